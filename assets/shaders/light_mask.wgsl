@@ -0,0 +1,10 @@
+// Multiplied over the screen by `LightMaskMaterial`, see lighting.rs
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(1) @binding(0) var mask_texture: texture_2d<f32>;
+@group(1) @binding(1) var mask_sampler: sampler;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(mask_texture, mask_sampler, mesh.uv);
+}
@@ -0,0 +1,667 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    collision::Layer,
+    combat::HitStun,
+    day_night::DayNight,
+    navigation::NavAgent,
+    perception::{AlertLevel, Awareness},
+    ron_asset::RonAssetLoader,
+    EnemyAttack, EnemyHealth, EnemyState, GameState, Player,
+};
+
+/// A behavior tree node
+/// Trees are re-run from the root every frame, nodes that need memory keep it in `AiState`
+#[derive(Deserialize, Clone, Debug)]
+pub enum Node {
+    /// Runs children in order until one doesn't fail
+    Selector(Vec<Node>),
+    /// Runs children in order until one doesn't succeed
+    Sequence(Vec<Node>),
+    /// Succeeds if the player is within `range` and no wall is in the way
+    LineOfSight {
+        range: f32,
+    },
+    /// Succeeds if the player is within the given distance
+    PlayerWithin(f32),
+    /// Succeeds if the enemy is at least this alert
+    Alert(AlertLevel),
+    /// Succeeds at night, for archetypes that only hunt in the dark
+    Night,
+    /// Succeeds if health is below the given fraction of what the enemy spawned with
+    HealthBelow(f32),
+    /// Walks back and forth up to `distance` away from where the enemy spawned
+    Patrol {
+        speed: f32,
+        distance: f32,
+    },
+    /// Walks to where the player was last seen or heard, succeeds once there
+    Investigate {
+        speed: f32,
+    },
+    /// Follows the navigation path to the player, or heads straight at them without one
+    Chase {
+        speed: f32,
+    },
+    /// Lets the enemy's attack hit this frame
+    Attack,
+    Flee {
+        speed: f32,
+    },
+    /// Stands still for a number of seconds
+    Wait(f32),
+}
+
+impl Node {
+    fn name(&self) -> &'static str {
+        match self {
+            Node::Selector(_) => "Selector",
+            Node::Sequence(_) => "Sequence",
+            Node::LineOfSight { .. } => "LineOfSight",
+            Node::PlayerWithin(_) => "PlayerWithin",
+            Node::Alert(_) => "Alert",
+            Node::Night => "Night",
+            Node::HealthBelow(_) => "HealthBelow",
+            Node::Patrol { .. } => "Patrol",
+            Node::Investigate { .. } => "Investigate",
+            Node::Chase { .. } => "Chase",
+            Node::Attack => "Attack",
+            Node::Flee { .. } => "Flee",
+            Node::Wait(_) => "Wait",
+        }
+    }
+
+    /// How many nodes are in the tree under and including this one
+    fn size(&self) -> usize {
+        match self {
+            Node::Selector(children) | Node::Sequence(children) => {
+                1 + children.iter().map(Node::size).sum::<usize>()
+            }
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+/// Behavior trees keyed by enemy archetype, loaded from `enemies.ai.ron`
+#[derive(Asset, TypePath, Deserialize)]
+pub struct BehaviorTrees(pub HashMap<String, Node>);
+
+#[derive(Resource)]
+struct BehaviorTreesHandle(Handle<BehaviorTrees>);
+
+/// Which behavior tree an enemy runs
+#[derive(Component, Clone)]
+pub struct AiArchetype(pub &'static str);
+
+/// Upwards speed of an enemy jumping along a navigation link
+/// Enough to clear the navigation graph's highest jump (3 cells of 8px) under default gravity
+const ENEMY_JUMP_SPEED: f32 = 190.0;
+
+/// Per enemy memory of the behavior tree
+#[derive(Default, Component)]
+pub struct AiState {
+    /// Name of the action that ran last frame, shown in the debug overlay
+    active: &'static str,
+    patrol_origin: Option<f32>,
+    patrol_direction: f32,
+    // Health the enemy had when the tree first ran
+    max_health: Option<i64>,
+    // Running `Wait` nodes, by the node's pre-order index in the tree
+    timers: HashMap<usize, Timer>,
+}
+
+/// Everything a tree needs to know about the world this frame
+struct Blackboard<'a> {
+    to_player: Option<Vec2>,
+    line_of_sight: &'a dyn Fn(f32) -> bool,
+    health_fraction: f32,
+    alert: AlertLevel,
+    to_last_known: Option<Vec2>,
+    night: bool,
+    /// Direction to the next waypoint and whether it needs a jump
+    next_move: Option<(f32, bool)>,
+    x: f32,
+    delta: std::time::Duration,
+}
+
+/// What the tree decided to do this frame
+#[derive(Default)]
+struct Decision {
+    speed: f32,
+    jump: bool,
+    attack: bool,
+    active: &'static str,
+    touched_timers: HashSet<usize>,
+}
+
+/// Runs `node` and its children
+/// `index` is the node's pre-order index and ends up past its whole subtree, even when children
+/// are skipped, so every node keeps the same index from frame to frame
+fn tick(
+    node: &Node,
+    index: &mut usize,
+    blackboard: &Blackboard,
+    state: &mut AiState,
+    decision: &mut Decision,
+) -> Status {
+    let id = *index;
+    *index += 1;
+
+    match node {
+        Node::Selector(children) => {
+            for (i, child) in children.iter().enumerate() {
+                let status = tick(child, index, blackboard, state, decision);
+
+                if status != Status::Failure {
+                    skip(&children[i + 1..], index);
+                    return status;
+                }
+            }
+
+            Status::Failure
+        }
+        Node::Sequence(children) => {
+            for (i, child) in children.iter().enumerate() {
+                let status = tick(child, index, blackboard, state, decision);
+
+                if status != Status::Success {
+                    skip(&children[i + 1..], index);
+                    return status;
+                }
+            }
+
+            Status::Success
+        }
+        Node::LineOfSight { range } => match blackboard.to_player {
+            Some(to_player) if to_player.length() <= *range => {
+                if (blackboard.line_of_sight)(to_player.length()) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            _ => Status::Failure,
+        },
+        Node::PlayerWithin(distance) => match blackboard.to_player {
+            Some(to_player) if to_player.length() <= *distance => Status::Success,
+            _ => Status::Failure,
+        },
+        Node::Alert(level) => {
+            if blackboard.alert >= *level {
+                Status::Success
+            } else {
+                Status::Failure
+            }
+        }
+        Node::Night => {
+            if blackboard.night {
+                Status::Success
+            } else {
+                Status::Failure
+            }
+        }
+        Node::HealthBelow(fraction) => {
+            if blackboard.health_fraction < *fraction {
+                Status::Success
+            } else {
+                Status::Failure
+            }
+        }
+        Node::Patrol { speed, distance } => {
+            let origin = *state.patrol_origin.get_or_insert(blackboard.x);
+
+            if state.patrol_direction == 0.0 {
+                state.patrol_direction = 1.0;
+            }
+
+            let offset = blackboard.x - origin;
+
+            if offset > *distance {
+                state.patrol_direction = -1.0;
+            } else if offset < -distance {
+                state.patrol_direction = 1.0;
+            }
+
+            decision.speed = state.patrol_direction * speed;
+            decision.active = node.name();
+
+            Status::Running
+        }
+        Node::Investigate { speed } => {
+            let Some(to_last_known) = blackboard.to_last_known else {
+                return Status::Failure;
+            };
+
+            decision.active = node.name();
+
+            if to_last_known.x.abs() < 4.0 {
+                return Status::Success;
+            }
+
+            decision.speed = to_last_known.x.signum() * speed;
+
+            Status::Running
+        }
+        Node::Chase { speed } => {
+            let Some(to_player) = blackboard.to_player else {
+                return Status::Failure;
+            };
+
+            match blackboard.next_move {
+                Some((direction, jump)) => {
+                    decision.speed = direction * speed;
+                    decision.jump = jump;
+                }
+                None => decision.speed = to_player.x.signum() * speed,
+            }
+
+            decision.active = node.name();
+
+            Status::Running
+        }
+        Node::Attack => {
+            decision.attack = true;
+            decision.active = node.name();
+
+            Status::Success
+        }
+        Node::Flee { speed } => {
+            let Some(to_player) = blackboard.to_player else {
+                return Status::Failure;
+            };
+
+            decision.speed = -to_player.x.signum() * speed;
+            decision.active = node.name();
+
+            Status::Running
+        }
+        Node::Wait(seconds) => {
+            let timer = state
+                .timers
+                .entry(id)
+                .or_insert_with(|| Timer::from_seconds(*seconds, TimerMode::Once));
+
+            timer.tick(blackboard.delta);
+            decision.active = node.name();
+
+            if timer.finished() {
+                state.timers.remove(&id);
+
+                Status::Success
+            } else {
+                decision.touched_timers.insert(id);
+
+                Status::Running
+            }
+        }
+    }
+}
+
+/// Moves `index` past children that didn't get to run
+fn skip(children: &[Node], index: &mut usize) {
+    *index += children.iter().map(Node::size).sum::<usize>();
+}
+
+/// Whether the node above each enemy is drawn, toggled with F3
+#[derive(Resource, Default)]
+struct AiDebug(bool);
+
+#[derive(Component)]
+struct AiDebugLabel;
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<BehaviorTrees>()
+            .register_asset_loader(RonAssetLoader::<BehaviorTrees>::new(&["ai.ron"]))
+            .init_resource::<AiDebug>()
+            .add_systems(Startup, load_behavior_trees)
+            .add_systems(
+                Update,
+                (
+                    run_behavior_trees.run_if(in_state(GameState::Playing)),
+                    toggle_ai_debug,
+                    update_ai_debug_labels,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn load_behavior_trees(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BehaviorTreesHandle(asset_server.load("enemies.ai.ron")));
+}
+
+fn run_behavior_trees(
+    mut enemies: Query<
+        (
+            Entity,
+            &AiArchetype,
+            &mut AiState,
+            &EnemyHealth,
+            &EnemyState,
+            &Transform,
+            &GlobalTransform,
+            &mut Velocity,
+            &mut EnemyAttack,
+            Option<&NavAgent>,
+            Option<&Awareness>,
+        ),
+        // Stunned enemies are left to their knockback
+        (Without<Player>, Without<HitStun>),
+    >,
+    player: Query<(Entity, &Transform, &GlobalTransform), With<Player>>,
+    trees_handle: Res<BehaviorTreesHandle>,
+    trees: Res<Assets<BehaviorTrees>>,
+    rapier_context: Res<RapierContext>,
+    day_night: Res<DayNight>,
+    time: Res<Time>,
+) {
+    let Some(trees) = trees.get(&trees_handle.0) else {
+        return;
+    };
+
+    let player = player.get_single().ok();
+
+    for (
+        entity,
+        archetype,
+        mut state,
+        health,
+        enemy_state,
+        transform,
+        global_transform,
+        mut velocity,
+        mut attack,
+        nav_agent,
+        awareness,
+    ) in enemies.iter_mut()
+    {
+        if let EnemyState::Dying | EnemyState::Dead(_) = enemy_state {
+            continue;
+        }
+
+        let Some(tree) = trees.0.get(archetype.0) else {
+            continue;
+        };
+
+        let max_health = *state.max_health.get_or_insert(health.0.max(1));
+
+        let origin = global_transform.translation().truncate();
+
+        // Walls between the enemy and the player block its view
+        let line_of_sight = |distance: f32| {
+            let Some((player_entity, _, player_global_transform)) = player else {
+                return false;
+            };
+
+            let to_player = player_global_transform.translation().truncate() - origin;
+
+            let filter = QueryFilter::default()
+                .groups(CollisionGroups::new(Group::ALL, Layer::World.group()))
+                .exclude_collider(entity)
+                .exclude_collider(player_entity)
+                .exclude_sensors();
+
+            rapier_context
+                .cast_ray(
+                    origin,
+                    to_player.normalize_or_zero(),
+                    distance,
+                    true,
+                    filter,
+                )
+                .is_none()
+        };
+
+        let blackboard = Blackboard {
+            to_player: player.map(|(_, player_transform, _)| {
+                (player_transform.translation - transform.translation).truncate()
+            }),
+            line_of_sight: &line_of_sight,
+            health_fraction: health.0 as f32 / max_health as f32,
+            alert: awareness.map_or(AlertLevel::Unaware, |awareness| awareness.level),
+            to_last_known: awareness
+                .and_then(|awareness| awareness.last_known)
+                .map(|position| position - origin),
+            night: day_night.is_night(),
+            next_move: nav_agent.and_then(NavAgent::next_move),
+            x: transform.translation.x,
+            delta: time.delta(),
+        };
+
+        let mut decision = Decision::default();
+
+        tick(tree, &mut 0, &blackboard, &mut state, &mut decision);
+
+        // Waits that didn't run this frame start over next time
+        state
+            .timers
+            .retain(|id, _| decision.touched_timers.contains(id));
+
+        state.active = decision.active;
+        velocity.linvel.x = decision.speed;
+
+        // Only jump off the ground, not again mid-air
+        if decision.jump && velocity.linvel.y.abs() < 1.0 {
+            velocity.linvel.y = ENEMY_JUMP_SPEED;
+        }
+
+        *attack = if decision.attack {
+            EnemyAttack::Attack
+        } else {
+            EnemyAttack::None
+        };
+    }
+}
+
+fn toggle_ai_debug(mut debug: ResMut<AiDebug>, keyb: Res<Input<KeyCode>>) {
+    if keyb.just_pressed(KeyCode::F3) {
+        debug.0 = !debug.0;
+    }
+}
+
+fn update_ai_debug_labels(
+    mut commands: Commands,
+    debug: Res<AiDebug>,
+    enemies: Query<(Entity, &AiState, Option<&Children>)>,
+    mut labels: Query<(Entity, &mut Text, &Parent), With<AiDebugLabel>>,
+) {
+    for (entity, _, parent) in labels.iter() {
+        // Dead enemies lose their `AiState`, their label goes with it
+        if !debug.0 || !enemies.contains(parent.get()) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    if !debug.0 {
+        return;
+    }
+
+    for (entity, state, children) in enemies.iter() {
+        let label = children
+            .into_iter()
+            .flatten()
+            .find(|child| labels.contains(**child));
+
+        match label {
+            Some(label) => {
+                if let Ok((_, mut text, _)) = labels.get_mut(*label) {
+                    text.sections[0].value = state.active.to_string();
+                }
+            }
+            None => {
+                commands.entity(entity).with_children(|enemy| {
+                    enemy.spawn((
+                        AiDebugLabel,
+                        Text2dBundle {
+                            text: Text::from_section(
+                                state.active,
+                                TextStyle {
+                                    font_size: 32.0,
+                                    color: Color::YELLOW,
+                                    ..Default::default()
+                                },
+                            ),
+                            // The camera is zoomed way in, shrink the text back down
+                            transform: Transform::from_xyz(0.0, 16.0, 10.0)
+                                .with_scale(Vec3::splat(0.2)),
+                            ..Default::default()
+                        },
+                    ));
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn clear_view(_: f32) -> bool {
+        true
+    }
+
+    fn blackboard(to_player: Vec2, night: bool, delta: f32) -> Blackboard<'static> {
+        Blackboard {
+            to_player: Some(to_player),
+            line_of_sight: &clear_view,
+            health_fraction: 1.0,
+            alert: AlertLevel::Unaware,
+            to_last_known: None,
+            night,
+            next_move: None,
+            x: 0.0,
+            delta: Duration::from_secs_f32(delta),
+        }
+    }
+
+    /// Runs one frame the way `run_behavior_trees` does
+    fn run(tree: &Node, blackboard: &Blackboard, state: &mut AiState) -> (Status, Decision) {
+        let mut decision = Decision::default();
+        let status = tick(tree, &mut 0, blackboard, state, &mut decision);
+
+        state
+            .timers
+            .retain(|id, _| decision.touched_timers.contains(id));
+
+        (status, decision)
+    }
+
+    #[test]
+    fn selector_stops_at_first_child_that_does_not_fail() {
+        let tree = Node::Selector(vec![Node::PlayerWithin(10.0), Node::Attack]);
+
+        let (status, decision) = run(
+            &tree,
+            &blackboard(Vec2::X * 5.0, false, 0.1),
+            &mut AiState::default(),
+        );
+        assert_eq!(status, Status::Success);
+        assert!(!decision.attack);
+
+        let (status, decision) = run(
+            &tree,
+            &blackboard(Vec2::X * 50.0, false, 0.1),
+            &mut AiState::default(),
+        );
+        assert_eq!(status, Status::Success);
+        assert!(decision.attack);
+    }
+
+    #[test]
+    fn sequence_stops_at_first_child_that_does_not_succeed() {
+        let tree = Node::Sequence(vec![Node::PlayerWithin(10.0), Node::Attack]);
+
+        let (status, decision) = run(
+            &tree,
+            &blackboard(Vec2::X * 50.0, false, 0.1),
+            &mut AiState::default(),
+        );
+        assert_eq!(status, Status::Failure);
+        assert!(!decision.attack);
+    }
+
+    #[test]
+    fn wait_runs_until_its_time_is_up() {
+        let tree = Node::Wait(1.0);
+        let mut state = AiState::default();
+
+        let (status, _) = run(&tree, &blackboard(Vec2::ZERO, false, 0.6), &mut state);
+        assert_eq!(status, Status::Running);
+
+        let (status, _) = run(&tree, &blackboard(Vec2::ZERO, false, 0.6), &mut state);
+        assert_eq!(status, Status::Success);
+        assert!(state.timers.is_empty());
+    }
+
+    #[test]
+    fn wait_keeps_its_timer_when_earlier_branches_change() {
+        // The selector takes a different branch every frame, the wait after it must not notice
+        let tree = Node::Sequence(vec![
+            Node::Selector(vec![Node::PlayerWithin(10.0), Node::Night]),
+            Node::Wait(1.0),
+        ]);
+        let mut state = AiState::default();
+
+        let (status, _) = run(&tree, &blackboard(Vec2::X * 5.0, true, 0.6), &mut state);
+        assert_eq!(status, Status::Running);
+
+        let (status, _) = run(&tree, &blackboard(Vec2::X * 50.0, true, 0.6), &mut state);
+        assert_eq!(status, Status::Success);
+    }
+
+    #[test]
+    fn lizard_in_range_bites_within_two_seconds() {
+        let trees: BehaviorTrees = ron::from_str(include_str!("../assets/enemies.ai.ron")).unwrap();
+        let lizard = &trees.0["lizard"];
+
+        let mut blackboard = blackboard(Vec2::X * 10.0, true, 1.0 / 60.0);
+        blackboard.alert = AlertLevel::Alerted;
+
+        let mut state = AiState::default();
+        let mut cooldown = crate::EnemyAttackCooldown::default();
+
+        // Two seconds of frames, the tree only says attack every so often after its wait
+        let bitten = (0..120).any(|_| {
+            let (_, decision) = run(lizard, &blackboard, &mut state);
+            let attack = if decision.attack {
+                EnemyAttack::Attack
+            } else {
+                EnemyAttack::None
+            };
+
+            cooldown.timer.tick(blackboard.delta);
+
+            cooldown.ready(Some(&attack))
+        });
+
+        assert!(bitten);
+    }
+
+    #[test]
+    fn size_counts_every_node() {
+        let tree = Node::Selector(vec![
+            Node::Sequence(vec![Node::Night, Node::Attack]),
+            Node::Wait(1.0),
+        ]);
+
+        assert_eq!(tree.size(), 5);
+    }
+}
@@ -0,0 +1,699 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    collision::{entity_collider, Layer},
+    combat::{Hit, HitStun, Knockback},
+    defense::{self, Blocking, Defense, STAGGER},
+    inventory::EquipmentStats,
+    leveling::ExperienceReward,
+    ron_asset::RonAssetLoader,
+    AnimationTimer, BulletBundle, BulletType, DeathAnimation, EnemyAttack, EnemyAttackCooldown,
+    EnemyDamage, EnemyHealth, EnemyState, GameState, Health, Invulnerable, Player, Stamina,
+};
+
+/// One move in a boss' attack pattern
+#[derive(Deserialize, Clone, Debug)]
+pub enum AttackStep {
+    /// Stand still for a number of seconds
+    Wait(f32),
+    /// Run at the player horizontally
+    Charge { speed: f32, duration: f32 },
+    /// Hit everything within `radius` at once
+    Slam { radius: f32, damage: i64 },
+    /// Fire `count` bullets fanned out over `spread` radians towards the player
+    Volley {
+        count: u32,
+        spread: f32,
+        speed: f32,
+        damage: i64,
+    },
+}
+
+impl AttackStep {
+    /// How long the boss is busy with this step
+    fn duration(&self) -> f32 {
+        match self {
+            AttackStep::Wait(seconds) => *seconds,
+            AttackStep::Charge { duration, .. } => *duration,
+            AttackStep::Slam { .. } => 0.8,
+            AttackStep::Volley { .. } => 0.6,
+        }
+    }
+}
+
+/// Slams throw the player back harder than a regular hit
+const SLAM_KNOCKBACK: Knockback = Knockback {
+    strength: 140.0,
+    stun: 0.5,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct BossPhase {
+    /// The phase starts once health drops to this fraction of max health
+    pub health_below: f32,
+    /// Played in order, then from the start again
+    pub pattern: Vec<AttackStep>,
+}
+
+/// A boss' name and phases, loaded from a `.boss.ron` file
+/// Phases are listed from full health downwards
+#[derive(Asset, TypePath, Deserialize)]
+pub struct BossDefinition {
+    pub name: String,
+    pub phases: Vec<BossPhase>,
+}
+
+#[derive(Default, Component)]
+pub struct Boss {
+    definition: String,
+    max_health: i64,
+}
+
+impl Boss {
+    const DEFAULT_HEALTH: i64 = 1200;
+
+    fn from_entity_instance(entity_instance: &EntityInstance) -> Self {
+        Self {
+            definition: entity_instance
+                .get_string_field("definition")
+                .cloned()
+                .unwrap_or_else(|_| "nightborne.boss.ron".to_string()),
+            max_health: boss_health(entity_instance).0,
+        }
+    }
+}
+
+fn boss_health(entity_instance: &EntityInstance) -> EnemyHealth {
+    EnemyHealth(
+        entity_instance
+            .get_int_field("health")
+            .map(|health| *health as i64)
+            .unwrap_or(Boss::DEFAULT_HEALTH),
+    )
+}
+
+/// Where the boss is in its current phase' pattern
+#[derive(Component)]
+struct BossBrain {
+    definition: Handle<BossDefinition>,
+    phase: usize,
+    step: usize,
+    // None until the current step has been started
+    step_timer: Option<Timer>,
+}
+
+#[derive(Bundle, LdtkEntity)]
+struct BossBundle {
+    #[with(Boss::from_entity_instance)]
+    boss: Boss,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[grid_coords]
+    grid_coords: GridCoords,
+    collider: Collider,
+    rigid_body: RigidBody,
+    lock_axes: LockedAxes,
+    velocity: Velocity,
+    read_mass: ReadMassProperties,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
+    // Contact damage, only switched on by `run_boss_pattern` while the fight is on
+    attack: EnemyAttack,
+    damage: EnemyDamage,
+    #[with(boss_health)]
+    health: EnemyHealth,
+    enemy_attack_cooldown: EnemyAttackCooldown,
+    state: EnemyState,
+    death_animation: DeathAnimation,
+    animation_timer: AnimationTimer,
+    experience_reward: ExperienceReward,
+}
+
+impl Default for BossBundle {
+    fn default() -> Self {
+        Self {
+            boss: Boss::default(),
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            grid_coords: GridCoords::default(),
+            collider: Collider::cuboid(14.0, 20.0),
+            rigid_body: RigidBody::Dynamic,
+            lock_axes: LockedAxes::ROTATION_LOCKED,
+            velocity: Velocity::zero(),
+            read_mass: ReadMassProperties::default(),
+            collision_group: Layer::Enemy.collision_groups(),
+            solver_group: Layer::Enemy.solver_groups(),
+            attack: EnemyAttack::None,
+            damage: EnemyDamage(30),
+            health: EnemyHealth(Boss::DEFAULT_HEALTH),
+            enemy_attack_cooldown: EnemyAttackCooldown::default(),
+            state: EnemyState::default(),
+            death_animation: DeathAnimation {
+                first: 92,
+                frames: 23,
+            },
+            animation_timer: AnimationTimer::default(),
+            experience_reward: ExperienceReward(500),
+        }
+    }
+}
+
+/// Entering this region starts the fight
+#[derive(Default, Component)]
+struct BossArena;
+
+#[derive(Bundle, LdtkEntity)]
+struct BossArenaBundle {
+    arena: BossArena,
+    #[with(entity_collider)]
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for BossArenaBundle {
+    fn default() -> Self {
+        Self {
+            arena: BossArena,
+            collider: Collider::cuboid(8.0, 8.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+/// Blocks an arena exit while the fight is on
+#[derive(Default, Component)]
+struct BossGate;
+
+#[derive(Bundle, LdtkEntity)]
+struct BossGateBundle {
+    gate: BossGate,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(entity_collider)]
+    collider: Collider,
+    // Gates stay open until the fight starts
+    disabled: ColliderDisabled,
+    rigid_body: RigidBody,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
+}
+
+impl Default for BossGateBundle {
+    fn default() -> Self {
+        Self {
+            gate: BossGate,
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::cuboid(4.0, 8.0),
+            disabled: ColliderDisabled,
+            rigid_body: RigidBody::Fixed,
+            collision_group: Layer::World.collision_groups(),
+            solver_group: Layer::World.solver_groups(),
+        }
+    }
+}
+
+#[derive(Resource, Default, PartialEq)]
+enum BossFight {
+    #[default]
+    Waiting,
+    Active,
+    Won,
+}
+
+#[derive(Component)]
+struct BossHealthBar;
+
+#[derive(Component)]
+struct BossHealthFill;
+
+#[derive(Component)]
+struct VictoryScreen;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<BossDefinition>()
+            .register_asset_loader(RonAssetLoader::<BossDefinition>::new(&["boss.ron"]))
+            .init_resource::<BossFight>()
+            .add_systems(
+                Update,
+                (
+                    reset_boss_fight,
+                    setup_bosses,
+                    hide_open_gates,
+                    start_boss_fight,
+                    run_boss_pattern,
+                    update_boss_health_bar,
+                    check_boss_defeated,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::Victory), spawn_victory_screen)
+            .register_ldtk_entity::<BossBundle>("Boss")
+            .register_ldtk_entity::<BossArenaBundle>("BossArena")
+            .register_ldtk_entity::<BossGateBundle>("BossGate");
+    }
+}
+
+/// A respawned or reloaded arena gets a fresh fight
+fn reset_boss_fight(
+    mut commands: Commands,
+    mut fight: ResMut<BossFight>,
+    arenas: Query<(), Added<BossArena>>,
+    health_bars: Query<Entity, With<BossHealthBar>>,
+) {
+    if arenas.is_empty() {
+        return;
+    }
+
+    *fight = BossFight::Waiting;
+
+    for entity in health_bars.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn setup_bosses(
+    mut commands: Commands,
+    bosses: Query<(Entity, &Boss), Added<Boss>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (entity, boss) in bosses.iter() {
+        commands.entity(entity).insert(BossBrain {
+            definition: asset_server.load(boss.definition.clone()),
+            phase: 0,
+            step: 0,
+            step_timer: None,
+        });
+    }
+}
+
+fn hide_open_gates(mut gates: Query<&mut Visibility, Added<BossGate>>) {
+    for mut visibility in gates.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn start_boss_fight(
+    mut commands: Commands,
+    mut fight: ResMut<BossFight>,
+    player: Query<Entity, With<Player>>,
+    arenas: Query<Entity, With<BossArena>>,
+    gates: Query<Entity, With<BossGate>>,
+    bosses: Query<&BossBrain>,
+    definitions: Res<Assets<BossDefinition>>,
+    rapier_context: Res<RapierContext>,
+) {
+    if *fight != BossFight::Waiting {
+        return;
+    }
+
+    let Ok(player_entity) = player.get_single() else {
+        return;
+    };
+
+    let entered = arenas
+        .iter()
+        .any(|arena| rapier_context.intersection_pair(player_entity, arena) == Some(true));
+
+    if !entered {
+        return;
+    }
+
+    *fight = BossFight::Active;
+
+    for gate in gates.iter() {
+        commands
+            .entity(gate)
+            .remove::<ColliderDisabled>()
+            .insert(Visibility::Visible);
+    }
+
+    let name = bosses
+        .iter()
+        .find_map(|brain| definitions.get(&brain.definition))
+        .map(|definition| definition.name.clone())
+        .unwrap_or_default();
+
+    commands
+        .spawn((
+            BossHealthBar,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(24.0),
+                    width: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(4.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ))
+        .with_children(|bar| {
+            bar.spawn(TextBundle::from_section(
+                name,
+                TextStyle {
+                    font_size: 22.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+            ));
+
+            bar.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Percent(60.0),
+                    height: Val::Px(14.0),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..Default::default()
+            })
+            .with_children(|background| {
+                background.spawn((
+                    BossHealthFill,
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            ..Default::default()
+                        },
+                        background_color: Color::MAROON.into(),
+                        ..Default::default()
+                    },
+                ));
+            });
+        });
+}
+
+fn run_boss_pattern(
+    mut commands: Commands,
+    fight: Res<BossFight>,
+    mut bosses: Query<
+        (
+            Entity,
+            &Boss,
+            &mut BossBrain,
+            &EnemyHealth,
+            &EnemyState,
+            &Transform,
+            &GlobalTransform,
+            &Parent,
+            &mut Velocity,
+            Has<HitStun>,
+            &mut EnemyAttack,
+        ),
+        Without<Player>,
+    >,
+    mut player: Query<
+        (
+            Entity,
+            &Transform,
+            &GlobalTransform,
+            &mut Health,
+            &mut Stamina,
+            &EquipmentStats,
+            &TextureAtlasSprite,
+            Option<&Blocking>,
+            Has<Invulnerable>,
+        ),
+        With<Player>,
+    >,
+    definitions: Res<Assets<BossDefinition>>,
+    time: Res<Time>,
+    mut hits: EventWriter<Hit>,
+) {
+    if *fight != BossFight::Active {
+        // A dormant boss doesn't hurt anyone who walks into it
+        for (.., mut attack) in bosses.iter_mut() {
+            *attack = EnemyAttack::None;
+        }
+
+        return;
+    }
+
+    let Ok((
+        player_entity,
+        player_transform,
+        player_global_transform,
+        mut player_health,
+        mut stamina,
+        stats,
+        sprite,
+        blocking,
+        invulnerable,
+    )) = player.get_single_mut()
+    else {
+        return;
+    };
+
+    for (
+        boss_entity,
+        boss,
+        mut brain,
+        health,
+        state,
+        transform,
+        global_transform,
+        parent,
+        mut velocity,
+        stunned,
+        mut attack,
+    ) in bosses.iter_mut()
+    {
+        if let EnemyState::Dying | EnemyState::Dead(_) = state {
+            continue;
+        }
+
+        // Hit-stun keeps it from hurting the player on contact, like any other enemy
+        *attack = if stunned {
+            EnemyAttack::None
+        } else {
+            EnemyAttack::Attack
+        };
+
+        let Some(definition) = definitions.get(&brain.definition) else {
+            continue;
+        };
+
+        let fraction = health.0 as f32 / boss.max_health.max(1) as f32;
+
+        let phase = definition
+            .phases
+            .iter()
+            .rposition(|phase| fraction <= phase.health_below)
+            .unwrap_or(0);
+
+        // Every phase starts its pattern from the top
+        if phase != brain.phase {
+            brain.phase = phase;
+            brain.step = 0;
+            brain.step_timer = None;
+            velocity.linvel.x = 0.0;
+        }
+
+        let Some(pattern) = definition.phases.get(phase).map(|phase| &phase.pattern) else {
+            continue;
+        };
+
+        if pattern.is_empty() {
+            continue;
+        }
+
+        let step = &pattern[brain.step % pattern.len()];
+
+        if let Some(timer) = brain.step_timer.as_mut() {
+            timer.tick(time.delta());
+
+            if timer.finished() {
+                brain.step = (brain.step + 1) % pattern.len();
+                brain.step_timer = None;
+                velocity.linvel.x = 0.0;
+            }
+
+            continue;
+        }
+
+        let to_player = (player_transform.translation - transform.translation).truncate();
+
+        match *step {
+            AttackStep::Wait(_) => {}
+            AttackStep::Charge { speed, .. } => {
+                velocity.linvel.x = to_player.x.signum() * speed;
+            }
+            AttackStep::Slam { radius, damage } => {
+                if to_player.length() <= radius && !invulnerable {
+                    let blocked = stats.0.armor.clamp(0.0, 1.0);
+                    let damage = (damage as f32 * (1.0 - blocked)).round() as i64;
+
+                    let source = global_transform.translation().truncate();
+
+                    // Blocked and parried like any other hit
+                    match defense::defend(
+                        blocking,
+                        sprite.flip_x,
+                        player_transform.translation.x,
+                        transform.translation.x,
+                        damage,
+                        stamina.current,
+                    ) {
+                        Defense::Parried => {
+                            hits.send(Hit {
+                                target: boss_entity,
+                                source: player_global_transform.translation().truncate(),
+                                knockback: STAGGER,
+                            });
+                        }
+                        Defense::Blocked {
+                            damage,
+                            stamina: cost,
+                        } => {
+                            player_health.current -= damage;
+                            stamina.current -= cost;
+
+                            hits.send(Hit {
+                                target: player_entity,
+                                source,
+                                knockback: SLAM_KNOCKBACK.scaled(0.5),
+                            });
+                        }
+                        Defense::Hit => {
+                            player_health.current -= damage;
+
+                            hits.send(Hit {
+                                target: player_entity,
+                                source,
+                                knockback: SLAM_KNOCKBACK,
+                            });
+                        }
+                    }
+                }
+            }
+            AttackStep::Volley {
+                count,
+                spread,
+                speed,
+                damage,
+            } => {
+                let aim = to_player.normalize_or_zero();
+
+                commands.entity(parent.get()).with_children(|parent| {
+                    for i in 0..count {
+                        let offset = if count > 1 {
+                            spread * (i as f32 / (count - 1) as f32 - 0.5)
+                        } else {
+                            0.0
+                        };
+
+                        let direction = Vec2::from_angle(offset).rotate(aim);
+
+                        parent.spawn(BulletBundle::new(
+                            BulletType::Enemy,
+                            damage,
+                            transform.translation,
+                            direction * speed,
+                        ));
+                    }
+                });
+            }
+        }
+
+        brain.step_timer = Some(Timer::from_seconds(step.duration(), TimerMode::Once));
+    }
+}
+
+fn update_boss_health_bar(
+    bosses: Query<(&Boss, &EnemyHealth)>,
+    mut fill: Query<&mut Style, With<BossHealthFill>>,
+) {
+    let Ok(mut style) = fill.get_single_mut() else {
+        return;
+    };
+
+    let (health, max_health) = bosses
+        .iter()
+        .fold((0, 0), |(health, max), (boss, boss_health)| {
+            (health + boss_health.0.max(0), max + boss.max_health)
+        });
+
+    let fraction = health as f32 / max_health.max(1) as f32;
+
+    style.width = Val::Percent(fraction.min(1.0) * 100.0);
+}
+
+fn check_boss_defeated(
+    mut commands: Commands,
+    mut fight: ResMut<BossFight>,
+    bosses: Query<&EnemyState, With<Boss>>,
+    gates: Query<Entity, With<BossGate>>,
+    health_bars: Query<Entity, With<BossHealthBar>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if *fight != BossFight::Active {
+        return;
+    }
+
+    // Let the death animation play out before calling it
+    let defeated = !bosses.is_empty()
+        && bosses
+            .iter()
+            .all(|state| matches!(state, EnemyState::Dead(_)));
+
+    if !defeated {
+        return;
+    }
+
+    *fight = BossFight::Won;
+
+    for entity in gates.iter().chain(health_bars.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    next_state.set(GameState::Victory);
+}
+
+fn spawn_victory_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            VictoryScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.0),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+                ..Default::default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn(TextBundle::from_section(
+                "Victory!",
+                TextStyle {
+                    font_size: 48.0,
+                    color: Color::GOLD,
+                    ..Default::default()
+                },
+            ));
+
+            screen.spawn(TextBundle::from_section(
+                "The darkness lifts. Thanks for playing.",
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+            ));
+        });
+}
@@ -0,0 +1,157 @@
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+/// Every kind of collider in the game
+/// Each layer owns exactly one collision group bit
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layer {
+    Player,
+    Enemy,
+    PlayerProjectile,
+    EnemyProjectile,
+    World,
+    Sensor,
+}
+
+/// How two layers react to each other
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Interaction {
+    /// Collision events and a physical contact response
+    Solid,
+    /// Collision events only, the bodies pass through each other
+    Detect,
+}
+
+// The interaction matrix. Pairs are symmetric and anything not listed here ignores each other.
+const INTERACTIONS: &[(Layer, Layer, Interaction)] = &[
+    (Layer::Player, Layer::World, Interaction::Solid),
+    (Layer::Player, Layer::Enemy, Interaction::Solid),
+    (Layer::Player, Layer::EnemyProjectile, Interaction::Detect),
+    (Layer::Player, Layer::Sensor, Interaction::Detect),
+    (Layer::Enemy, Layer::World, Interaction::Solid),
+    (Layer::Enemy, Layer::PlayerProjectile, Interaction::Detect),
+    (Layer::PlayerProjectile, Layer::World, Interaction::Detect),
+    (Layer::EnemyProjectile, Layer::World, Interaction::Detect),
+];
+
+impl Layer {
+    pub fn group(self) -> Group {
+        match self {
+            Layer::World => Group::GROUP_1,
+            Layer::Player => Group::GROUP_2,
+            Layer::Enemy => Group::GROUP_3,
+            Layer::PlayerProjectile => Group::GROUP_4,
+            Layer::EnemyProjectile => Group::GROUP_5,
+            Layer::Sensor => Group::GROUP_6,
+        }
+    }
+
+    /// Groups of every layer this one interacts with in the given way
+    fn partners(self, solid_only: bool) -> Group {
+        INTERACTIONS
+            .iter()
+            .filter(|(_, _, interaction)| !solid_only || *interaction == Interaction::Solid)
+            .filter_map(|&(a, b, _)| {
+                if a == self {
+                    Some(b)
+                } else if b == self {
+                    Some(a)
+                } else {
+                    None
+                }
+            })
+            .fold(Group::NONE, |groups, layer| groups | layer.group())
+    }
+
+    /// Which colliders this layer detects at all
+    pub fn collision_groups(self) -> CollisionGroups {
+        CollisionGroups::new(self.group(), self.partners(false))
+    }
+
+    /// Which colliders this layer gets pushed around by
+    pub fn solver_groups(self) -> SolverGroups {
+        SolverGroups::new(self.group(), self.partners(true))
+    }
+}
+
+/// A box collider covering an LDtk entity's full size
+pub fn entity_collider(entity_instance: &EntityInstance) -> Collider {
+    Collider::cuboid(
+        entity_instance.width as f32 / 2.0,
+        entity_instance.height as f32 / 2.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYERS: [Layer; 6] = [
+        Layer::Player,
+        Layer::Enemy,
+        Layer::PlayerProjectile,
+        Layer::EnemyProjectile,
+        Layer::World,
+        Layer::Sensor,
+    ];
+
+    fn collides(a: Layer, b: Layer) -> bool {
+        let (a, b) = (a.collision_groups(), b.collision_groups());
+
+        a.memberships.intersects(b.filters) && b.memberships.intersects(a.filters)
+    }
+
+    fn solid(a: Layer, b: Layer) -> bool {
+        let (a, b) = (a.solver_groups(), b.solver_groups());
+
+        a.memberships.intersects(b.filters) && b.memberships.intersects(a.filters)
+    }
+
+    #[test]
+    fn every_layer_has_its_own_group() {
+        for (i, a) in LAYERS.iter().enumerate() {
+            assert_eq!(a.group().bits().count_ones(), 1);
+
+            for b in &LAYERS[i + 1..] {
+                assert_ne!(a.group(), b.group());
+            }
+        }
+    }
+
+    #[test]
+    fn interactions_are_symmetric() {
+        for a in LAYERS {
+            for b in LAYERS {
+                let filters = a.collision_groups().filters;
+
+                assert_eq!(
+                    filters.contains(b.group()),
+                    b.collision_groups().filters.contains(a.group()),
+                    "{a:?} and {b:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn player_is_blocked_by_the_world_and_enemies() {
+        assert!(solid(Layer::Player, Layer::World));
+        assert!(solid(Layer::Player, Layer::Enemy));
+    }
+
+    #[test]
+    fn projectiles_and_sensors_only_detect() {
+        assert!(collides(Layer::Player, Layer::EnemyProjectile));
+        assert!(!solid(Layer::Player, Layer::EnemyProjectile));
+
+        assert!(collides(Layer::Player, Layer::Sensor));
+        assert!(!solid(Layer::Player, Layer::Sensor));
+    }
+
+    #[test]
+    fn unlisted_pairs_ignore_each_other() {
+        assert!(!collides(Layer::Player, Layer::PlayerProjectile));
+        assert!(!collides(Layer::Enemy, Layer::Enemy));
+        assert!(!collides(Layer::World, Layer::World));
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{Animation, EnemyAttack, GameState, Player, PlayerAttack};
+
+/// How hard an attacker's hits push their target back and for how long it's stunned
+#[derive(Component, Clone, Copy)]
+pub struct Knockback {
+    /// Speed the target gets knocked away with
+    pub strength: f32,
+    /// Seconds the target can't move or attack
+    pub stun: f32,
+}
+
+impl Default for Knockback {
+    fn default() -> Self {
+        Self {
+            strength: 80.0,
+            stun: 0.3,
+        }
+    }
+}
+
+impl Knockback {
+    pub fn scaled(self, factor: f32) -> Self {
+        Self {
+            strength: self.strength * factor,
+            stun: self.stun * factor,
+        }
+    }
+}
+
+/// Something got hit by an attack coming from `source`
+#[derive(Event)]
+pub struct Hit {
+    pub target: Entity,
+    pub source: Vec2,
+    pub knockback: Knockback,
+}
+
+/// Blocks input and attacks until it runs out
+#[derive(Component)]
+pub struct HitStun(Timer);
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Hit>().add_systems(
+            Update,
+            (apply_hits, recover_from_hit_stun)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+fn apply_hits(
+    mut commands: Commands,
+    mut hits: EventReader<Hit>,
+    targets: Query<(&GlobalTransform, &ReadMassProperties)>,
+    mut player: Query<(&mut Animation, &mut PlayerAttack), With<Player>>,
+    mut enemies: Query<&mut EnemyAttack, Without<Player>>,
+) {
+    for hit in hits.read() {
+        let Ok((transform, mass)) = targets.get(hit.target) else {
+            continue;
+        };
+
+        // Always push sideways and a little up, so nothing gets driven into the floor
+        let away = transform.translation().x - hit.source.x;
+        let direction = Vec2::new(if away < 0.0 { -1.0 } else { 1.0 }, 0.5).normalize();
+
+        // Strength is a speed, so heavier targets need a bigger push to get knocked back as far
+        commands.entity(hit.target).insert((
+            ExternalImpulse {
+                impulse: direction * hit.knockback.strength * mass.get().mass,
+                torque_impulse: 0.0,
+            },
+            HitStun(Timer::from_seconds(hit.knockback.stun, TimerMode::Once)),
+        ));
+
+        if let Ok((mut animation, mut attack)) = player.get_mut(hit.target) {
+            if !matches!(*animation, Animation::Death(_) | Animation::Dead) {
+                *animation = Animation::Hurt(0);
+            }
+
+            *attack = PlayerAttack::None;
+        }
+
+        if let Ok(mut attack) = enemies.get_mut(hit.target) {
+            *attack = EnemyAttack::None;
+        }
+    }
+}
+
+fn recover_from_hit_stun(
+    mut commands: Commands,
+    mut stunned: Query<(Entity, &mut HitStun)>,
+    time: Res<Time>,
+) {
+    for (entity, mut stun) in stunned.iter_mut() {
+        stun.0.tick(time.delta());
+
+        if stun.0.finished() {
+            commands.entity(entity).remove::<HitStun>();
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{GameState, MainCamera};
+
+/// The LDtk layer that's only shown at night
+const NIGHT_LAYER: &str = "Night";
+
+/// Everything gets washed in this at full darkness
+const NIGHT_TINT: Color = Color::rgba(0.05, 0.05, 0.2, 0.55);
+
+/// Where we are in the day, the cycle starts at noon
+#[derive(Resource)]
+pub struct DayNight {
+    elapsed: f32,
+    /// Seconds for a full day and night
+    length: f32,
+}
+
+impl Default for DayNight {
+    fn default() -> Self {
+        Self {
+            elapsed: 0.0,
+            length: 240.0,
+        }
+    }
+}
+
+impl DayNight {
+    /// 0 at noon, 1 at midnight
+    pub fn darkness(&self) -> f32 {
+        let phase = self.elapsed / self.length;
+
+        0.5 - 0.5 * (phase * TAU).cos()
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.darkness() > 0.5
+    }
+}
+
+/// Full screen tint in front of the world, follows the camera
+#[derive(Component)]
+struct NightOverlay;
+
+pub struct DayNightPlugin;
+
+impl Plugin for DayNightPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DayNight>()
+            .add_systems(Update, spawn_night_overlay)
+            .add_systems(
+                Update,
+                (advance_time, tint_night, toggle_night_layer)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+fn spawn_night_overlay(mut commands: Commands, cameras: Query<Entity, Added<MainCamera>>) {
+    for camera in cameras.iter() {
+        commands.entity(camera).with_children(|camera| {
+            camera.spawn((
+                NightOverlay,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::NONE,
+                        // Comfortably bigger than the zoomed in view
+                        custom_size: Some(Vec2::splat(4096.0)),
+                        ..Default::default()
+                    },
+                    // Just in front of the camera's near plane so it covers the whole world
+                    transform: Transform::from_xyz(0.0, 0.0, -1.0),
+                    ..Default::default()
+                },
+            ));
+        });
+    }
+}
+
+fn advance_time(mut day_night: ResMut<DayNight>, time: Res<Time>) {
+    let length = day_night.length;
+    day_night.elapsed = (day_night.elapsed + time.delta_seconds()) % length;
+}
+
+fn tint_night(mut overlay: Query<&mut Sprite, With<NightOverlay>>, day_night: Res<DayNight>) {
+    let alpha = NIGHT_TINT.a() * day_night.darkness();
+
+    for mut sprite in overlay.iter_mut() {
+        sprite.color = NIGHT_TINT.with_a(alpha);
+    }
+}
+
+/// The Night layer pops in at dusk and out at dawn
+fn toggle_night_layer(
+    mut layers: Query<(&LayerMetadata, &mut Visibility)>,
+    day_night: Res<DayNight>,
+) {
+    let visibility = if day_night.is_night() {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    for (layer, mut layer_visibility) in layers.iter_mut() {
+        if layer.identifier == NIGHT_LAYER && *layer_visibility != visibility {
+            *layer_visibility = visibility;
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    combat::{HitStun, Knockback},
+    Animation, GameState, Player,
+};
+
+/// How long after raising the guard a hit counts as parried
+const PARRY_WINDOW: f32 = 0.15;
+/// Fraction of a blocked hit's damage that still gets through
+const BLOCKED_DAMAGE: f32 = 0.25;
+
+/// What a parried melee attacker gets hit with
+pub const STAGGER: Knockback = Knockback {
+    strength: 60.0,
+    stun: 1.0,
+};
+
+const BLOCKING_TINT: Color = Color::rgb(0.7, 0.8, 1.0);
+
+/// The player is holding up their guard
+#[derive(Component)]
+pub struct Blocking {
+    parry: Timer,
+}
+
+impl Blocking {
+    pub fn parrying(&self) -> bool {
+        !self.parry.finished()
+    }
+}
+
+/// How the player's guard dealt with an incoming hit
+pub enum Defense {
+    /// Caught in the parry window, no damage at all
+    Parried,
+    /// Damage was reduced at the cost of some stamina
+    Blocked { damage: i64, stamina: i64 },
+    /// The hit landed in full
+    Hit,
+}
+
+/// Works out a hit of `damage` coming from `source_x` against the player at `player_x`
+/// Only hits from the side the player faces can be blocked, and only with enough stamina left
+pub fn defend(
+    blocking: Option<&Blocking>,
+    facing_left: bool,
+    player_x: f32,
+    source_x: f32,
+    damage: i64,
+    stamina: i64,
+) -> Defense {
+    let Some(blocking) = blocking else {
+        return Defense::Hit;
+    };
+
+    let from_left = source_x < player_x;
+
+    if from_left != facing_left {
+        return Defense::Hit;
+    }
+
+    if blocking.parrying() {
+        return Defense::Parried;
+    }
+
+    let taken = (damage as f32 * BLOCKED_DAMAGE).round() as i64;
+    let cost = damage - taken;
+
+    // Not enough stamina breaks the guard
+    if stamina < cost {
+        return Defense::Hit;
+    }
+
+    Defense::Blocked {
+        damage: taken,
+        stamina: cost,
+    }
+}
+
+pub struct DefensePlugin;
+
+impl Plugin for DefensePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            handle_block_input.run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// Holding S blocks, the guard drops when it's released or the player gets stunned
+fn handle_block_input(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &Animation,
+            &mut Velocity,
+            &mut TextureAtlasSprite,
+            Option<&mut Blocking>,
+            Has<HitStun>,
+        ),
+        With<Player>,
+    >,
+    keyb: Res<Input<KeyCode>>,
+    time: Res<Time>,
+) {
+    let Ok((entity, animation, mut velocity, mut sprite, blocking, stunned)) =
+        player.get_single_mut()
+    else {
+        return;
+    };
+
+    let can_block = matches!(animation, Animation::Run(_)) && !stunned;
+
+    match blocking {
+        Some(mut blocking) => {
+            if !keyb.pressed(KeyCode::S) || !can_block {
+                commands.entity(entity).remove::<Blocking>();
+                sprite.color = Color::WHITE;
+
+                return;
+            }
+
+            blocking.parry.tick(time.delta());
+
+            sprite.color = if blocking.parrying() {
+                Color::GOLD
+            } else {
+                BLOCKING_TINT
+            };
+        }
+        None => {
+            if keyb.just_pressed(KeyCode::S) && can_block {
+                commands.entity(entity).insert(Blocking {
+                    parry: Timer::from_seconds(PARRY_WINDOW, TimerMode::Once),
+                });
+
+                // Plant your feet to block
+                velocity.linvel.x = 0.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn guard(parrying: bool) -> Blocking {
+        let mut blocking = Blocking {
+            parry: Timer::from_seconds(PARRY_WINDOW, TimerMode::Once),
+        };
+
+        if !parrying {
+            blocking.parry.tick(Duration::from_secs_f32(PARRY_WINDOW));
+        }
+
+        blocking
+    }
+
+    #[test]
+    fn no_guard_takes_the_hit() {
+        assert!(matches!(
+            defend(None, false, 0.0, 10.0, 40, 100),
+            Defense::Hit
+        ));
+    }
+
+    #[test]
+    fn blocks_hits_from_the_front() {
+        // Facing right, hit from the right
+        assert!(matches!(
+            defend(Some(&guard(false)), false, 0.0, 10.0, 40, 100),
+            Defense::Blocked {
+                damage: 10,
+                stamina: 30
+            }
+        ));
+    }
+
+    #[test]
+    fn hits_from_behind_get_through() {
+        // Facing left, hit from the right
+        assert!(matches!(
+            defend(Some(&guard(false)), true, 0.0, 10.0, 40, 100),
+            Defense::Hit
+        ));
+        assert!(matches!(
+            defend(Some(&guard(true)), true, 0.0, 10.0, 40, 100),
+            Defense::Hit
+        ));
+    }
+
+    #[test]
+    fn parries_right_after_raising_the_guard() {
+        assert!(matches!(
+            defend(Some(&guard(true)), true, 10.0, 0.0, 40, 0),
+            Defense::Parried
+        ));
+    }
+
+    #[test]
+    fn guard_breaks_without_stamina() {
+        assert!(matches!(
+            defend(Some(&guard(false)), false, 0.0, 10.0, 40, 29),
+            Defense::Hit
+        ));
+    }
+}
@@ -0,0 +1,274 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    collision::{entity_collider, Layer},
+    inventory::EquipmentStats,
+    particles::{Emitter, ParticleSettings},
+    Debufs, DebufsEnum, GameState, Health, Player,
+};
+
+/// IntGrid value of lava tiles in the light and dark side layers
+const LAVA: i32 = 4;
+
+/// Health lost per second to each debuff, before resistances
+const POISON_DAMAGE: f32 = 3.0;
+const FIRE_DAMAGE: f32 = 6.0;
+
+/// Fire goes out on its own after this long, poison lasts until it's cured
+const BURN_TIME: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug)]
+struct VentCycle {
+    on: f32,
+    off: f32,
+}
+
+/// Applies `debuf` to the player while they're inside it and it's active
+#[derive(Component)]
+pub struct Hazard {
+    debuf: DebufsEnum,
+    active: bool,
+    /// Fire vents switch on and off on a timer, everything else is always active
+    cycle: Option<(VentCycle, Timer)>,
+}
+
+impl Hazard {
+    fn new(debuf: DebufsEnum) -> Self {
+        Self {
+            debuf,
+            active: true,
+            cycle: None,
+        }
+    }
+}
+
+fn poison_gas(_: &EntityInstance) -> Hazard {
+    Hazard::new(DebufsEnum::Poison)
+}
+
+fn fire_vent(entity_instance: &EntityInstance) -> Hazard {
+    let seconds = |field: &str, default: f32| {
+        entity_instance
+            .get_float_field(field)
+            .copied()
+            .unwrap_or(default)
+            .max(0.1)
+    };
+
+    let cycle = VentCycle {
+        on: seconds("on_time", 1.5),
+        off: seconds("off_time", 2.0),
+    };
+
+    Hazard {
+        active: false,
+        cycle: Some((cycle, Timer::from_seconds(cycle.off, TimerMode::Once))),
+        ..Hazard::new(DebufsEnum::Fire)
+    }
+}
+
+/// Particles all over the hazard's area, denser areas get more of them
+fn area_emitter(entity_instance: &EntityInstance, settings: ParticleSettings) -> Emitter {
+    let half_size = Vec2::new(entity_instance.width as f32, entity_instance.height as f32) / 2.0;
+
+    Emitter::new(
+        settings,
+        (half_size.x * half_size.y / 16.0).max(2.0),
+        half_size,
+    )
+}
+
+fn poison_gas_emitter(entity_instance: &EntityInstance) -> Emitter {
+    area_emitter(entity_instance, ParticleSettings::POISON)
+}
+
+fn fire_vent_emitter(entity_instance: &EntityInstance) -> Emitter {
+    let mut emitter = area_emitter(entity_instance, ParticleSettings::FIRE);
+
+    // Vents start out off
+    emitter.enabled = false;
+    emitter
+}
+
+#[derive(Bundle, LdtkEntity)]
+struct PoisonGasBundle {
+    #[with(poison_gas)]
+    hazard: Hazard,
+    #[with(poison_gas_emitter)]
+    emitter: Emitter,
+    #[with(entity_collider)]
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for PoisonGasBundle {
+    fn default() -> Self {
+        Self {
+            hazard: Hazard::new(DebufsEnum::Poison),
+            emitter: Emitter::new(ParticleSettings::POISON, 2.0, Vec2::splat(8.0)),
+            collider: Collider::cuboid(8.0, 8.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+#[derive(Bundle, LdtkEntity)]
+struct FireVentBundle {
+    #[with(fire_vent)]
+    hazard: Hazard,
+    #[with(fire_vent_emitter)]
+    emitter: Emitter,
+    #[with(entity_collider)]
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for FireVentBundle {
+    fn default() -> Self {
+        Self {
+            hazard: Hazard::new(DebufsEnum::Fire),
+            emitter: Emitter::new(ParticleSettings::FIRE, 2.0, Vec2::splat(8.0)),
+            collider: Collider::cuboid(8.0, 8.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+#[derive(Bundle, LdtkIntCell)]
+struct LavaBundle {
+    hazard: Hazard,
+    emitter: Emitter,
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for LavaBundle {
+    fn default() -> Self {
+        Self {
+            hazard: Hazard::new(DebufsEnum::Fire),
+            // Every lava tile is its own hazard, keep the particles sparse
+            emitter: Emitter::new(ParticleSettings::FIRE, 0.5, Vec2::splat(4.0)),
+            collider: Collider::cuboid(4.0, 4.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+/// Damage over time from the player's debuffs
+#[derive(Component)]
+pub struct DebufTimers {
+    tick: Timer,
+    burn: Timer,
+}
+
+impl Default for DebufTimers {
+    fn default() -> Self {
+        Self {
+            tick: Timer::from_seconds(1.0, TimerMode::Repeating),
+            burn: Timer::from_seconds(BURN_TIME, TimerMode::Once),
+        }
+    }
+}
+
+pub struct HazardsPlugin;
+
+impl Plugin for HazardsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (cycle_fire_vents, apply_hazards, hurt_debuffed)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        )
+        .register_ldtk_entity::<PoisonGasBundle>("PoisonGas")
+        .register_ldtk_entity::<FireVentBundle>("FireVent")
+        .register_ldtk_int_cell_for_layer::<LavaBundle>("The_dark_side", LAVA)
+        .register_ldtk_int_cell_for_layer::<LavaBundle>("The_light_side", LAVA);
+    }
+}
+
+fn cycle_fire_vents(mut hazards: Query<(&mut Hazard, &mut Emitter)>, time: Res<Time>) {
+    for (mut hazard, mut emitter) in hazards.iter_mut() {
+        let hazard = &mut *hazard;
+
+        let Some((cycle, timer)) = &mut hazard.cycle else {
+            continue;
+        };
+
+        timer.tick(time.delta());
+
+        if timer.finished() {
+            hazard.active = !hazard.active;
+            emitter.enabled = hazard.active;
+
+            let seconds = if hazard.active { cycle.on } else { cycle.off };
+            *timer = Timer::from_seconds(seconds, TimerMode::Once);
+        }
+    }
+}
+
+fn apply_hazards(
+    hazards: Query<(Entity, &Hazard)>,
+    mut player: Query<(Entity, &mut Debufs, &mut DebufTimers), With<Player>>,
+    rapier_context: Res<RapierContext>,
+) {
+    let Ok((player_entity, mut debufs, mut timers)) = player.get_single_mut() else {
+        return;
+    };
+
+    for (entity, hazard) in hazards.iter() {
+        if !hazard.active || rapier_context.intersection_pair(player_entity, entity) != Some(true) {
+            continue;
+        }
+
+        if !debufs.debufs.contains(&hazard.debuf) {
+            debufs.debufs.push(hazard.debuf);
+        }
+
+        // Staying in the flames keeps the player burning
+        if hazard.debuf == DebufsEnum::Fire {
+            timers.burn.reset();
+        }
+    }
+}
+
+fn hurt_debuffed(
+    mut player: Query<(&mut Health, &mut Debufs, &mut DebufTimers, &EquipmentStats), With<Player>>,
+    time: Res<Time>,
+) {
+    let Ok((mut health, mut debufs, mut timers, stats)) = player.get_single_mut() else {
+        return;
+    };
+
+    if debufs.debufs.contains(&DebufsEnum::Fire) {
+        timers.burn.tick(time.delta());
+
+        if timers.burn.finished() {
+            debufs.debufs.retain(|debuf| *debuf != DebufsEnum::Fire);
+        }
+    }
+
+    timers.tick.tick(time.delta());
+
+    if !timers.tick.just_finished() {
+        return;
+    }
+
+    for debuf in &debufs.debufs {
+        let (damage, resistance) = match debuf {
+            DebufsEnum::Poison => (POISON_DAMAGE, stats.0.poison_resistance),
+            DebufsEnum::Fire => (FIRE_DAMAGE, stats.0.fire_resistance),
+            DebufsEnum::None => continue,
+        };
+
+        health.current -= (damage * (1.0 - resistance.clamp(0.0, 1.0))).round() as i64;
+    }
+}
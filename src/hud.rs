@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+
+use crate::{Health, Player, Stamina};
+
+/// Which stat a bar (or its label) shows
+#[derive(Clone, Copy)]
+enum HudBar {
+    Health,
+    Stamina,
+}
+
+impl HudBar {
+    fn color(self) -> Color {
+        match self {
+            HudBar::Health => Color::CRIMSON,
+            HudBar::Stamina => Color::LIME_GREEN,
+        }
+    }
+}
+
+/// The filled part of a bar
+#[derive(Component)]
+struct HudFill(HudBar);
+
+/// The `current/max` text next to a bar
+#[derive(Component)]
+struct HudLabel(HudBar);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud)
+            .add_systems(Update, update_hud);
+    }
+}
+
+fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(12.0),
+                top: Val::Px(12.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with_children(|hud| {
+            for bar in [HudBar::Health, HudBar::Stamina] {
+                hud.spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|row| {
+                    row.spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(160.0),
+                            height: Val::Px(12.0),
+                            ..Default::default()
+                        },
+                        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                        ..Default::default()
+                    })
+                    .with_children(|background| {
+                        background.spawn((
+                            HudFill(bar),
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(100.0),
+                                    height: Val::Percent(100.0),
+                                    ..Default::default()
+                                },
+                                background_color: bar.color().into(),
+                                ..Default::default()
+                            },
+                        ));
+                    });
+
+                    row.spawn((
+                        HudLabel(bar),
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 16.0,
+                                color: Color::WHITE,
+                                ..Default::default()
+                            },
+                        ),
+                    ));
+                });
+            }
+        });
+}
+
+fn update_hud(
+    player: Query<(&Health, &Stamina), With<Player>>,
+    mut fills: Query<(&HudFill, &mut Style)>,
+    mut labels: Query<(&HudLabel, &mut Text)>,
+) {
+    let Ok((health, stamina)) = player.get_single() else {
+        return;
+    };
+
+    let values = |bar: HudBar| match bar {
+        HudBar::Health => (health.current, health.max),
+        HudBar::Stamina => (stamina.current, stamina.max),
+    };
+
+    for (fill, mut style) in fills.iter_mut() {
+        let (current, max) = values(fill.0);
+        let fraction = current.max(0) as f32 / max.max(1) as f32;
+
+        style.width = Val::Percent(fraction.min(1.0) * 100.0);
+    }
+
+    for (label, mut text) in labels.iter_mut() {
+        let (current, max) = values(label.0);
+
+        text.sections[0].value = format!("{}/{}", current.max(0), max);
+    }
+}
@@ -0,0 +1,390 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    collision::{entity_collider, Layer},
+    GameState, Player,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SwitchKind {
+    /// Flipped on and off with the interaction key, remembered in the save
+    Lever,
+    /// Pressed with the interaction key, turns itself off again after a while
+    Button,
+    /// On while the player stands on it
+    PressurePlate,
+}
+
+/// Something that sends a signal to the doors and gates in its `targets` field
+#[derive(Component)]
+pub struct Switch {
+    kind: SwitchKind,
+    iid: String,
+    // Buttons pop back out when this runs out
+    timer: Timer,
+}
+
+impl Switch {
+    const DEFAULT_BUTTON_TIME: f32 = 3.0;
+
+    /// The kind of switch comes from the entity's identifier
+    fn from_entity_instance(entity_instance: &EntityInstance) -> Self {
+        let kind = match entity_instance.identifier.as_str() {
+            "Button" => SwitchKind::Button,
+            "PressurePlate" => SwitchKind::PressurePlate,
+            _ => SwitchKind::Lever,
+        };
+
+        let seconds = entity_instance
+            .get_float_field("duration")
+            .copied()
+            .unwrap_or(Self::DEFAULT_BUTTON_TIME);
+
+        Self {
+            kind,
+            iid: entity_instance.iid.clone(),
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ReceiverKind {
+    /// Opens while any of its switches is on
+    Door,
+    /// Opens only while all of its switches are on
+    Gate,
+}
+
+/// A door or gate other entities' `targets` fields point at
+#[derive(Component)]
+pub struct Receiver {
+    kind: ReceiverKind,
+    iid: String,
+    open: bool,
+}
+
+impl Receiver {
+    /// The kind of receiver comes from the entity's identifier
+    fn from_entity_instance(entity_instance: &EntityInstance) -> Self {
+        let kind = match entity_instance.identifier.as_str() {
+            "Gate" => ReceiverKind::Gate,
+            _ => ReceiverKind::Door,
+        };
+
+        Self {
+            kind,
+            iid: entity_instance.iid.clone(),
+            open: false,
+        }
+    }
+}
+
+/// Switch states and how everything is wired, by LDtk entity iid
+/// Wiring comes from the whole project, so switches work on doors in levels that aren't loaded
+#[derive(Resource, Default)]
+pub struct Signals {
+    on: HashMap<String, bool>,
+    /// Switches wired to each door or gate
+    inputs: HashMap<String, Vec<String>>,
+    /// The level each lever lives in, levers are saved per level
+    lever_levels: HashMap<String, String>,
+}
+
+impl Signals {
+    fn is_on(&self, iid: &str) -> bool {
+        self.on.get(iid).copied().unwrap_or(false)
+    }
+
+    fn set(&mut self, iid: &str, on: bool) {
+        // Don't trip change detection when nothing changed
+        if self.is_on(iid) != on {
+            self.on.insert(iid.to_string(), on);
+        }
+    }
+
+    /// Every lever's state as (level iid, lever iid, on)
+    pub fn levers(&self) -> impl Iterator<Item = (&str, &str, bool)> {
+        self.lever_levels
+            .iter()
+            .map(|(lever, level)| (level.as_str(), lever.as_str(), self.is_on(lever)))
+    }
+
+    pub fn restore_lever(&mut self, iid: &str, on: bool) {
+        self.on.insert(iid.to_string(), on);
+    }
+}
+
+/// A lever was flipped, worth saving
+#[derive(Event)]
+pub struct LeverPulled;
+
+/// Levers, buttons and pressure plates
+#[derive(Bundle, LdtkEntity)]
+struct SwitchBundle {
+    #[with(Switch::from_entity_instance)]
+    switch: Switch,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(entity_collider)]
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for SwitchBundle {
+    fn default() -> Self {
+        Self {
+            switch: Switch {
+                kind: SwitchKind::Lever,
+                iid: String::new(),
+                timer: Timer::default(),
+            },
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::cuboid(4.0, 4.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+/// Doors and gates
+#[derive(Bundle, LdtkEntity)]
+struct ReceiverBundle {
+    #[with(Receiver::from_entity_instance)]
+    receiver: Receiver,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(entity_collider)]
+    collider: Collider,
+    rigid_body: RigidBody,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
+}
+
+impl Default for ReceiverBundle {
+    fn default() -> Self {
+        Self {
+            receiver: Receiver {
+                kind: ReceiverKind::Door,
+                iid: String::new(),
+                open: false,
+            },
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::cuboid(4.0, 8.0),
+            rigid_body: RigidBody::Fixed,
+            collision_group: Layer::World.collision_groups(),
+            solver_group: Layer::World.solver_groups(),
+        }
+    }
+}
+
+pub struct InteractionsPlugin;
+
+impl Plugin for InteractionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Signals>()
+            .add_event::<LeverPulled>()
+            .add_systems(Update, wire_signals)
+            .add_systems(
+                Update,
+                (
+                    interact,
+                    release_buttons,
+                    press_pressure_plates,
+                    show_switches,
+                    update_receivers,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .register_ldtk_entity::<SwitchBundle>("Lever")
+            .register_ldtk_entity::<SwitchBundle>("Button")
+            .register_ldtk_entity::<SwitchBundle>("PressurePlate")
+            .register_ldtk_entity::<ReceiverBundle>("Door")
+            .register_ldtk_entity::<ReceiverBundle>("Gate");
+    }
+}
+
+/// Rebuilds the wiring from every switch's `targets` field whenever the project (re)loads
+fn wire_signals(
+    mut signals: ResMut<Signals>,
+    mut asset_events: EventReader<AssetEvent<LdtkProject>>,
+    projects: Res<Assets<LdtkProject>>,
+) {
+    for event in asset_events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+
+        let Some(project) = projects.get(*id) else {
+            continue;
+        };
+
+        signals.inputs.clear();
+        signals.lever_levels.clear();
+
+        for level in &project.json_data().levels {
+            let entities = level
+                .layer_instances
+                .iter()
+                .flatten()
+                .flat_map(|layer| &layer.entity_instances);
+
+            for entity in entities {
+                let Ok(targets) = entity.get_maybe_entity_refs_field("targets") else {
+                    continue;
+                };
+
+                for target in targets.iter().flatten() {
+                    signals
+                        .inputs
+                        .entry(target.entity_iid.clone())
+                        .or_default()
+                        .push(entity.iid.clone());
+                }
+
+                if entity.identifier == "Lever" {
+                    signals
+                        .lever_levels
+                        .insert(entity.iid.clone(), level.iid.clone());
+                }
+            }
+        }
+    }
+}
+
+/// E uses whichever lever or button the player is standing at
+fn interact(
+    mut switches: Query<(Entity, &mut Switch)>,
+    player: Query<Entity, With<Player>>,
+    mut signals: ResMut<Signals>,
+    mut lever_pulled: EventWriter<LeverPulled>,
+    keyb: Res<Input<KeyCode>>,
+    rapier_context: Res<RapierContext>,
+) {
+    if !keyb.just_pressed(KeyCode::E) {
+        return;
+    }
+
+    let Ok(player_entity) = player.get_single() else {
+        return;
+    };
+
+    let touched = switches.iter_mut().find(|(entity, switch)| {
+        switch.kind != SwitchKind::PressurePlate
+            && rapier_context.intersection_pair(player_entity, *entity) == Some(true)
+    });
+
+    let Some((_, mut switch)) = touched else {
+        return;
+    };
+
+    match switch.kind {
+        SwitchKind::Lever => {
+            let on = !signals.is_on(&switch.iid);
+            signals.set(&switch.iid, on);
+            lever_pulled.send(LeverPulled);
+        }
+        SwitchKind::Button => {
+            signals.set(&switch.iid, true);
+            switch.timer.reset();
+        }
+        SwitchKind::PressurePlate => {}
+    }
+}
+
+fn release_buttons(
+    mut switches: Query<&mut Switch>,
+    mut signals: ResMut<Signals>,
+    time: Res<Time>,
+) {
+    for mut switch in switches.iter_mut() {
+        if switch.kind != SwitchKind::Button || !signals.is_on(&switch.iid) {
+            continue;
+        }
+
+        switch.timer.tick(time.delta());
+
+        if switch.timer.finished() {
+            signals.set(&switch.iid, false);
+        }
+    }
+}
+
+fn press_pressure_plates(
+    switches: Query<(Entity, &Switch)>,
+    player: Query<Entity, With<Player>>,
+    mut signals: ResMut<Signals>,
+    rapier_context: Res<RapierContext>,
+) {
+    let player = player.get_single().ok();
+
+    for (entity, switch) in switches.iter() {
+        if switch.kind != SwitchKind::PressurePlate {
+            continue;
+        }
+
+        let pressed = player
+            .is_some_and(|player| rapier_context.intersection_pair(player, entity) == Some(true));
+
+        signals.set(&switch.iid, pressed);
+    }
+}
+
+/// Levers flip over and buttons and plates darken while they're on
+fn show_switches(mut switches: Query<(&Switch, &mut TextureAtlasSprite)>, signals: Res<Signals>) {
+    for (switch, mut sprite) in switches.iter_mut() {
+        let on = signals.is_on(&switch.iid);
+
+        match switch.kind {
+            SwitchKind::Lever => sprite.flip_x = on,
+            SwitchKind::Button | SwitchKind::PressurePlate => {
+                sprite.color = if on { Color::GRAY } else { Color::WHITE };
+            }
+        }
+    }
+}
+
+fn update_receivers(
+    mut commands: Commands,
+    mut receivers: Query<(Entity, &mut Receiver)>,
+    signals: Res<Signals>,
+) {
+    for (entity, mut receiver) in receivers.iter_mut() {
+        let mut inputs = signals
+            .inputs
+            .get(&receiver.iid)
+            .into_iter()
+            .flatten()
+            .map(|input| signals.is_on(input))
+            .peekable();
+
+        // Nothing wired up means nothing can open it
+        let open = inputs.peek().is_some()
+            && match receiver.kind {
+                ReceiverKind::Door => inputs.any(|on| on),
+                ReceiverKind::Gate => inputs.all(|on| on),
+            };
+
+        if open == receiver.open {
+            continue;
+        }
+
+        receiver.open = open;
+
+        if open {
+            commands
+                .entity(entity)
+                .insert((ColliderDisabled, Visibility::Hidden));
+        } else {
+            commands
+                .entity(entity)
+                .remove::<ColliderDisabled>()
+                .insert(Visibility::Visible);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::{ron_asset::RonAssetLoader, GameState, Player};
+
+/// Where an item goes when it gets equipped
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Slot {
+    Weapon,
+    Armor,
+}
+
+/// Stat changes an item gives while it's equipped
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct Modifiers {
+    /// Added to the damage of every attack
+    pub damage: i64,
+    /// Added to the reach of every attack
+    pub range: f32,
+    /// Added to the stamina cost of dashing and attacking, negative values make them cheaper
+    pub stamina_cost: i64,
+    /// Fraction of enemy damage that gets blocked
+    pub armor: f32,
+    pub poison_resistance: f32,
+    pub fire_resistance: f32,
+    pub max_health: i64,
+    pub max_stamina: i64,
+}
+
+impl Modifiers {
+    fn combine(&mut self, other: &Modifiers) {
+        self.damage += other.damage;
+        self.range += other.range;
+        self.stamina_cost += other.stamina_cost;
+        self.armor += other.armor;
+        self.poison_resistance += other.poison_resistance;
+        self.fire_resistance += other.fire_resistance;
+        self.max_health += other.max_health;
+        self.max_stamina += other.max_stamina;
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ItemDefinition {
+    pub name: String,
+    /// Items without a slot can be carried but not equipped
+    #[serde(default)]
+    pub slot: Option<Slot>,
+    #[serde(default)]
+    pub modifiers: Modifiers,
+}
+
+/// Every item in the game keyed by its id, loaded from `items.items.ron`
+#[derive(Asset, TypePath, Deserialize)]
+pub struct ItemDefinitions(pub HashMap<String, ItemDefinition>);
+
+#[derive(Resource)]
+struct ItemDefinitionsHandle(Handle<ItemDefinitions>);
+
+/// Ids of every item the player carries
+#[derive(Default, Component)]
+pub struct Inventory {
+    pub items: Vec<String>,
+}
+
+/// Ids of the items the player has equipped
+#[derive(Default, Component)]
+pub struct Equipment {
+    pub weapon: Option<String>,
+    pub armor: Option<String>,
+}
+
+impl Equipment {
+    fn slot_mut(&mut self, slot: Slot) -> &mut Option<String> {
+        match slot {
+            Slot::Weapon => &mut self.weapon,
+            Slot::Armor => &mut self.armor,
+        }
+    }
+
+    fn is_equipped(&self, item: &str) -> bool {
+        self.weapon.as_deref() == Some(item) || self.armor.as_deref() == Some(item)
+    }
+}
+
+/// The combined modifiers of everything the player has equipped
+#[derive(Default, Component)]
+pub struct EquipmentStats(pub Modifiers);
+
+#[derive(Component)]
+struct InventoryScreen;
+
+/// Index into `Inventory::items` of the item a button equips
+#[derive(Component)]
+struct ItemButton(usize);
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ItemDefinitions>()
+            .register_asset_loader(RonAssetLoader::<ItemDefinitions>::new(&["items.ron"]))
+            .add_systems(Startup, load_item_definitions)
+            .add_systems(Update, update_equipment_stats)
+            .add_systems(OnEnter(GameState::Paused), spawn_inventory_screen)
+            .add_systems(OnExit(GameState::Paused), despawn_inventory_screen)
+            .add_systems(
+                Update,
+                (equip_clicked_item, redraw_inventory_screen)
+                    .chain()
+                    .run_if(in_state(GameState::Paused)),
+            );
+    }
+}
+
+fn load_item_definitions(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ItemDefinitionsHandle(asset_server.load("items.items.ron")));
+}
+
+fn update_equipment_stats(
+    mut player: Query<(Ref<Equipment>, &mut EquipmentStats), With<Player>>,
+    mut asset_events: EventReader<AssetEvent<ItemDefinitions>>,
+    definitions_handle: Res<ItemDefinitionsHandle>,
+    definitions: Res<Assets<ItemDefinitions>>,
+) {
+    // Definitions (re)loading changes every item at once
+    let definitions_changed = asset_events.read().count() > 0;
+
+    let Some(definitions) = definitions.get(&definitions_handle.0) else {
+        return;
+    };
+
+    for (equipment, mut stats) in player.iter_mut() {
+        if !equipment.is_changed() && !definitions_changed {
+            continue;
+        }
+
+        let mut modifiers = Modifiers::default();
+
+        for item in [&equipment.weapon, &equipment.armor].into_iter().flatten() {
+            if let Some(definition) = definitions.0.get(item) {
+                modifiers.combine(&definition.modifiers);
+            }
+        }
+
+        stats.0 = modifiers;
+    }
+}
+
+fn spawn_inventory_screen(
+    mut commands: Commands,
+    player: Query<(&Inventory, &Equipment), With<Player>>,
+    definitions_handle: Res<ItemDefinitionsHandle>,
+    definitions: Res<Assets<ItemDefinitions>>,
+) {
+    if let Ok((inventory, equipment)) = player.get_single() {
+        build_inventory_screen(
+            &mut commands,
+            inventory,
+            equipment,
+            definitions.get(&definitions_handle.0),
+        );
+    }
+}
+
+fn build_inventory_screen(
+    commands: &mut Commands,
+    inventory: &Inventory,
+    equipment: &Equipment,
+    definitions: Option<&ItemDefinitions>,
+) {
+    let name_of = |item: &str| {
+        definitions
+            .and_then(|definitions| definitions.0.get(item))
+            .map(|definition| definition.name.clone())
+            .unwrap_or_else(|| item.to_string())
+    };
+
+    let text_style = TextStyle {
+        font_size: 20.0,
+        color: Color::WHITE,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            InventoryScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(8.0),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                ..Default::default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn(TextBundle::from_section(
+                "Paused - Inventory",
+                TextStyle {
+                    font_size: 32.0,
+                    ..text_style.clone()
+                },
+            ));
+
+            let equipped = |slot: &Option<String>| {
+                slot.as_deref()
+                    .map(name_of)
+                    .unwrap_or_else(|| "-".to_string())
+            };
+
+            screen.spawn(TextBundle::from_section(
+                format!(
+                    "Weapon: {}    Armor: {}",
+                    equipped(&equipment.weapon),
+                    equipped(&equipment.armor)
+                ),
+                text_style.clone(),
+            ));
+
+            if inventory.items.is_empty() {
+                screen.spawn(TextBundle::from_section(
+                    "Nothing here yet",
+                    text_style.clone(),
+                ));
+            }
+
+            for (i, item) in inventory.items.iter().enumerate() {
+                let marker = if equipment.is_equipped(item) {
+                    "[E] "
+                } else {
+                    ""
+                };
+
+                screen
+                    .spawn((
+                        ItemButton(i),
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::axes(Val::Px(12.0), Val::Px(4.0)),
+                                ..Default::default()
+                            },
+                            background_color: Color::rgb(0.2, 0.2, 0.25).into(),
+                            ..Default::default()
+                        },
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            format!("{}{}", marker, name_of(item)),
+                            text_style.clone(),
+                        ));
+                    });
+            }
+        });
+}
+
+fn despawn_inventory_screen(mut commands: Commands, screen: Query<Entity, With<InventoryScreen>>) {
+    for entity in screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn equip_clicked_item(
+    mut player: Query<(&Inventory, &mut Equipment), With<Player>>,
+    buttons: Query<(&Interaction, &ItemButton), Changed<Interaction>>,
+    definitions_handle: Res<ItemDefinitionsHandle>,
+    definitions: Res<Assets<ItemDefinitions>>,
+) {
+    let Ok((inventory, mut equipment)) = player.get_single_mut() else {
+        return;
+    };
+
+    let Some(definitions) = definitions.get(&definitions_handle.0) else {
+        return;
+    };
+
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let Some(item) = inventory.items.get(button.0) else {
+            continue;
+        };
+
+        let Some(slot) = definitions
+            .0
+            .get(item)
+            .and_then(|definition| definition.slot)
+        else {
+            continue;
+        };
+
+        // Clicking an equipped item takes it off again
+        let slot = equipment.slot_mut(slot);
+
+        if slot.as_deref() == Some(item) {
+            *slot = None;
+        } else {
+            *slot = Some(item.clone());
+        }
+    }
+}
+
+fn redraw_inventory_screen(
+    mut commands: Commands,
+    screen: Query<Entity, With<InventoryScreen>>,
+    player: Query<
+        (&Inventory, &Equipment),
+        (With<Player>, Or<(Changed<Inventory>, Changed<Equipment>)>),
+    >,
+    definitions_handle: Res<ItemDefinitionsHandle>,
+    definitions: Res<Assets<ItemDefinitions>>,
+) {
+    let Ok((inventory, equipment)) = player.get_single() else {
+        return;
+    };
+
+    for entity in screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    build_inventory_screen(
+        &mut commands,
+        inventory,
+        equipment,
+        definitions.get(&definitions_handle.0),
+    );
+}
@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+
+use crate::{EnemyDied, GameState, Level, Player};
+
+/// Experience an enemy is worth when it dies
+#[derive(Component, Clone)]
+pub struct ExperienceReward(pub u64);
+
+/// Sent every time the player gains a level
+#[derive(Event)]
+pub struct LevelUp {
+    pub level: u8,
+}
+
+#[derive(Component)]
+struct LevelUpNotification {
+    timer: Timer,
+}
+
+/// Experience needed to get from `level` to the next one
+pub fn experience_to_next_level(level: u8) -> u64 {
+    (50.0 * (level as f32).powf(1.5)).round() as u64
+}
+
+pub struct LevelingPlugin;
+
+impl Plugin for LevelingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelUp>().add_systems(
+            Update,
+            (
+                gain_experience.run_if(in_state(GameState::Playing)),
+                show_level_up_notification,
+                fade_level_up_notification,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn gain_experience(
+    mut died: EventReader<EnemyDied>,
+    rewards: Query<&ExperienceReward>,
+    mut player: Query<&mut Level, With<Player>>,
+    mut level_up: EventWriter<LevelUp>,
+) {
+    let Ok(mut level) = player.get_single_mut() else {
+        return;
+    };
+
+    for event in died.read() {
+        let Ok(reward) = rewards.get(event.enemy) else {
+            continue;
+        };
+
+        level.experience += reward.0;
+
+        while level.level < u8::MAX && level.experience >= experience_to_next_level(level.level) {
+            level.experience -= experience_to_next_level(level.level);
+            level.level += 1;
+
+            level_up.send(LevelUp { level: level.level });
+        }
+    }
+}
+
+fn show_level_up_notification(
+    mut commands: Commands,
+    mut level_up: EventReader<LevelUp>,
+    notifications: Query<Entity, With<LevelUpNotification>>,
+) {
+    // Only the newest level matters if several happen at once
+    let Some(event) = level_up.read().last() else {
+        return;
+    };
+
+    for entity in notifications.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands.spawn((
+        LevelUpNotification {
+            timer: Timer::from_seconds(2.5, TimerMode::Once),
+        },
+        TextBundle::from_section(
+            format!("Level up! You are now level {}", event.level),
+            TextStyle {
+                font_size: 28.0,
+                color: Color::GOLD,
+                ..Default::default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        })
+        .with_text_alignment(TextAlignment::Center),
+    ));
+}
+
+fn fade_level_up_notification(
+    mut commands: Commands,
+    mut notifications: Query<(Entity, &mut LevelUpNotification, &mut Text)>,
+    time: Res<Time>,
+) {
+    for (entity, mut notification, mut text) in notifications.iter_mut() {
+        notification.timer.tick(time.delta());
+
+        if notification.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let alpha = notification.timer.percent_left().min(0.5) * 2.0;
+
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(alpha);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_level_up_takes_fifty() {
+        assert_eq!(experience_to_next_level(1), 50);
+    }
+
+    #[test]
+    fn levels_get_steeper() {
+        assert_eq!(experience_to_next_level(4), 400);
+
+        for level in 1..u8::MAX {
+            assert!(experience_to_next_level(level + 1) > experience_to_next_level(level));
+        }
+    }
+}
@@ -0,0 +1,444 @@
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        mesh::MeshVertexBufferLayout,
+        render_resource::{
+            AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState, Extent3d,
+            RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError, TextureDimension,
+            TextureFormat, TextureUsages,
+        },
+        view::RenderLayers,
+    },
+    sprite::{Material2d, Material2dKey, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
+    transform::TransformSystem,
+    window::PrimaryWindow,
+};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{GameState, MainCamera, Player};
+
+/// Lights are drawn on their own render layer into the light mask, never straight to the screen
+const LIGHT_LAYER: u8 = 1;
+
+/// The light mask is drawn at a fraction of the window's resolution, lights are soft anyway
+const MASK_DOWNSCALE: f32 = 2.0;
+
+/// Ambient light on each side of the world, 1 is fully lit
+const LIGHT_SIDE_AMBIENT: f32 = 1.0;
+const DARK_SIDE_AMBIENT: f32 = 0.12;
+
+/// Pixels across the light falloff texture
+const FALLOFF_SIZE: u32 = 64;
+
+/// Whether a level is on the light or dark side of the world, levels on the dark side are
+/// barely lit without light sources
+/// Levels can set their own ambient light with an `ambient` float field
+#[derive(Component)]
+pub struct LightSide {
+    light_on: bool,
+    ambient: Option<f32>,
+}
+
+impl LightSide {
+    fn ambient(&self) -> f32 {
+        self.ambient.unwrap_or(if self.light_on {
+            LIGHT_SIDE_AMBIENT
+        } else {
+            DARK_SIDE_AMBIENT
+        })
+    }
+}
+
+/// A point light, lights up a circle of `radius` around its entity
+#[derive(Component, Clone, Copy)]
+pub struct Light2d {
+    pub radius: f32,
+    pub color: Color,
+    /// How much the radius wavers, as a fraction of it
+    pub flicker: f32,
+    /// Seconds the flicker is ahead by, so lights next to each other don't pulse in lockstep
+    pub phase: f32,
+}
+
+impl Default for Light2d {
+    fn default() -> Self {
+        Self {
+            radius: 40.0,
+            color: Color::rgb(1.0, 0.95, 0.85),
+            flicker: 0.0,
+            phase: 0.0,
+        }
+    }
+}
+
+fn torch_light(entity_instance: &EntityInstance) -> Light2d {
+    let iid_hash = entity_instance.iid.bytes().fold(0u32, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as u32)
+    });
+
+    Light2d {
+        radius: entity_instance
+            .get_float_field("radius")
+            .copied()
+            .unwrap_or(48.0),
+        color: Color::rgb(1.0, 0.7, 0.35),
+        flicker: 0.08,
+        // Up to ten seconds, picked from the iid so every torch keeps its own between runs
+        phase: (iid_hash % 1000) as f32 / 100.0,
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+struct TorchBundle {
+    #[with(torch_light)]
+    light: Light2d,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+}
+
+/// The sprite that draws a `Light2d` into the light mask
+#[derive(Component)]
+struct LightSprite;
+
+/// Renders the lights over the current level's ambient light into the light mask
+#[derive(Component)]
+struct LightCamera;
+
+/// Multiplies the light mask over everything the main camera sees
+#[derive(Component)]
+struct LightMaskOverlay;
+
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+struct LightMaskMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    mask: Handle<Image>,
+}
+
+impl Material2d for LightMaskMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/light_mask.wgsl".into()
+    }
+
+    /// Darkens what's already on screen instead of drawing over it
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let multiply = BlendComponent {
+            src_factor: BlendFactor::Dst,
+            dst_factor: BlendFactor::Zero,
+            operation: BlendOperation::Add,
+        };
+
+        if let Some(target) = descriptor
+            .fragment
+            .as_mut()
+            .and_then(|fragment| fragment.targets.first_mut())
+            .and_then(Option::as_mut)
+        {
+            target.blend = Some(BlendState {
+                color: multiply,
+                alpha: BlendComponent::OVER,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+struct LightAssets {
+    mask: Handle<Image>,
+    falloff: Handle<Image>,
+}
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<LightMaskMaterial>::default())
+            .add_systems(Startup, create_light_assets)
+            .add_systems(
+                Update,
+                (
+                    setup_light_mask,
+                    fit_light_mask,
+                    mark_level_sides,
+                    spawn_light_sprites,
+                ),
+            )
+            .add_systems(
+                Update,
+                (update_light_sprites, update_ambient_light).run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                PostUpdate,
+                follow_main_camera.before(TransformSystem::TransformPropagate),
+            )
+            .register_ldtk_entity::<TorchBundle>("Torch");
+    }
+}
+
+fn light_mask_image(width: u32, height: u32) -> Image {
+    let size = Extent3d {
+        width: width.max(1),
+        height: height.max(1),
+        depth_or_array_layers: 1,
+    };
+
+    let mut image = Image {
+        data: vec![255; (size.width * size.height * 4) as usize],
+        ..Default::default()
+    };
+
+    image.texture_descriptor.size = size;
+    image.texture_descriptor.format = TextureFormat::Bgra8UnormSrgb;
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+
+    image
+}
+
+/// A white circle fading out towards its edge, every light is drawn with it
+fn falloff_image() -> Image {
+    let center = (FALLOFF_SIZE as f32 - 1.0) / 2.0;
+
+    let data = (0..FALLOFF_SIZE * FALLOFF_SIZE)
+        .flat_map(|i| {
+            let offset = Vec2::new((i % FALLOFF_SIZE) as f32, (i / FALLOFF_SIZE) as f32) - center;
+            let falloff = (1.0 - offset.length() / center).clamp(0.0, 1.0);
+
+            [255, 255, 255, (falloff * falloff * 255.0) as u8]
+        })
+        .collect();
+
+    Image::new(
+        Extent3d {
+            width: FALLOFF_SIZE,
+            height: FALLOFF_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+fn create_light_assets(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.insert_resource(LightAssets {
+        mask: images.add(light_mask_image(1, 1)),
+        falloff: images.add(falloff_image()),
+    });
+}
+
+/// Hooks the light camera and the mask overlay up to the main camera once it exists
+fn setup_light_mask(
+    mut commands: Commands,
+    cameras: Query<(Entity, &OrthographicProjection), Added<MainCamera>>,
+    light_assets: Res<LightAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LightMaskMaterial>>,
+) {
+    for (entity, projection) in cameras.iter() {
+        let mut light_camera = Camera2dBundle {
+            camera: Camera {
+                // Before the main camera, so the mask is ready when it's drawn
+                order: -1,
+                target: RenderTarget::Image(light_assets.mask.clone()),
+                ..Default::default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(Color::WHITE),
+            },
+            ..Default::default()
+        };
+
+        light_camera.projection.scale = projection.scale * MASK_DOWNSCALE;
+
+        commands.spawn((
+            LightCamera,
+            light_camera,
+            RenderLayers::layer(LIGHT_LAYER),
+            UiCameraConfig { show_ui: false },
+        ));
+
+        commands.entity(entity).with_children(|camera| {
+            camera.spawn((
+                LightMaskOverlay,
+                MaterialMesh2dBundle {
+                    mesh: meshes.add(shape::Quad::new(Vec2::ONE).into()).into(),
+                    material: materials.add(LightMaskMaterial {
+                        mask: light_assets.mask.clone(),
+                    }),
+                    // Between the world and the night tint
+                    transform: Transform::from_xyz(0.0, 0.0, -2.0),
+                    ..Default::default()
+                },
+            ));
+        });
+    }
+}
+
+/// Keeps the light mask at the window's size, and the overlay covering the main camera's view
+fn fit_light_mask(
+    window: Query<Ref<Window>, With<PrimaryWindow>>,
+    camera: Query<&OrthographicProjection, With<MainCamera>>,
+    mut overlay: Query<(
+        Ref<LightMaskOverlay>,
+        &mut Mesh2dHandle,
+        &Handle<LightMaskMaterial>,
+    )>,
+    light_assets: Res<LightAssets>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LightMaskMaterial>>,
+) {
+    let (Ok(window), Ok(projection)) = (window.get_single(), camera.get_single()) else {
+        return;
+    };
+
+    let view = Vec2::new(window.width(), window.height()) * projection.scale;
+
+    for (overlay, mut mesh, material) in overlay.iter_mut() {
+        if !window.is_changed() && !overlay.is_added() {
+            continue;
+        }
+
+        let Some(mask) = images.get_mut(&light_assets.mask) else {
+            return;
+        };
+
+        let width = (window.width() / MASK_DOWNSCALE) as u32;
+        let height = (window.height() / MASK_DOWNSCALE) as u32;
+
+        if mask.size() != UVec2::new(width, height) {
+            *mask = light_mask_image(width, height);
+
+            // Rebinds the new mask texture
+            materials.get_mut(material);
+        }
+
+        *mesh = meshes.add(shape::Quad::new(view).into()).into();
+    }
+}
+
+/// Works out each level's side from which side's layer it has tiles in
+fn mark_level_sides(
+    mut commands: Commands,
+    levels: Query<(Entity, &LevelIid, &Parent), Added<LevelIid>>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    for (entity, level_iid, world) in levels.iter() {
+        // Each level looks itself up in the project of the world it's in
+        let Some(project) = ldtk_projects
+            .get(world.get())
+            .ok()
+            .and_then(|handle| ldtk_project_assets.get(handle))
+        else {
+            warn!(
+                level = %level_iid,
+                "Level isn't in a loaded LDtk world, leaving its lights alone"
+            );
+            continue;
+        };
+
+        let Some(level) = project.get_raw_level_by_iid(&level_iid.to_string()) else {
+            warn!(level = %level_iid, "Level is missing from its LDtk project");
+            continue;
+        };
+
+        let dark = level.layer_instances.iter().flatten().any(|layer| {
+            layer.identifier == "The_dark_side" && layer.int_grid_csv.iter().any(|cell| *cell != 0)
+        });
+
+        commands.entity(entity).insert(LightSide {
+            light_on: !dark,
+            ambient: level.get_float_field("ambient").ok().copied(),
+        });
+    }
+}
+
+fn spawn_light_sprites(
+    mut commands: Commands,
+    lights: Query<(Entity, &Light2d), Added<Light2d>>,
+    light_assets: Res<LightAssets>,
+) {
+    for (entity, light) in lights.iter() {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                LightSprite,
+                SpriteBundle {
+                    texture: light_assets.falloff.clone(),
+                    sprite: Sprite {
+                        color: light.color,
+                        custom_size: Some(Vec2::splat(light.radius * 2.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                RenderLayers::layer(LIGHT_LAYER),
+            ));
+        });
+    }
+}
+
+fn update_light_sprites(
+    lights: Query<(&Light2d, &Children)>,
+    mut sprites: Query<&mut Sprite, With<LightSprite>>,
+    time: Res<Time>,
+) {
+    let t = time.elapsed_seconds();
+
+    for (light, children) in lights.iter() {
+        // A couple of out of step waves read as a flame
+        let t = t + light.phase;
+        let flicker = 1.0 + light.flicker * ((t * 11.0).sin() * 0.6 + (t * 23.0).sin() * 0.4);
+
+        for child in children.iter() {
+            if let Ok(mut sprite) = sprites.get_mut(*child) {
+                sprite.color = light.color;
+                sprite.custom_size = Some(Vec2::splat(light.radius * 2.0 * flicker));
+            }
+        }
+    }
+}
+
+/// The light mask starts out as the ambient light of whatever level the player is in
+fn update_ambient_light(
+    mut light_camera: Query<&mut Camera2d, With<LightCamera>>,
+    player: Query<&Parent, With<Player>>,
+    layers: Query<&Parent>,
+    levels: Query<&LightSide>,
+) {
+    let Ok(mut camera) = light_camera.get_single_mut() else {
+        return;
+    };
+
+    // The player is in an entity layer, which is in the level
+    let ambient = player
+        .get_single()
+        .ok()
+        .and_then(|layer| layers.get(layer.get()).ok())
+        .and_then(|level| levels.get(level.get()).ok())
+        .map_or(LIGHT_SIDE_AMBIENT, LightSide::ambient);
+
+    camera.clear_color = ClearColorConfig::Custom(Color::rgb(ambient, ambient, ambient));
+}
+
+fn follow_main_camera(
+    mut light_camera: Query<&mut Transform, (With<LightCamera>, Without<MainCamera>)>,
+    main_camera: Query<&Transform, With<MainCamera>>,
+) {
+    let (Ok(mut light_transform), Ok(main_transform)) =
+        (light_camera.get_single_mut(), main_camera.get_single())
+    else {
+        return;
+    };
+
+    *light_transform = *main_transform;
+}
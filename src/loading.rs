@@ -0,0 +1,286 @@
+use bevy::{asset::RecursiveDependencyLoadState, prelude::*, utils::HashMap};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::GameState;
+
+/// A sprite sheet laid out in a grid of square tiles
+struct SheetConfig {
+    name: &'static str,
+    path: &'static str,
+    tile_size: f32,
+    columns: usize,
+    rows: usize,
+}
+
+/// Sprite sheets for things spawned outside of LDtk, laid out like their tilesets in the project
+const SPRITE_SHEETS: &[SheetConfig] = &[
+    SheetConfig {
+        name: "satyr",
+        path: "satyr-Sheet.png",
+        tile_size: 32.0,
+        columns: 9,
+        rows: 5,
+    },
+    SheetConfig {
+        name: "lizard",
+        path: "lizard_spritesheet.png",
+        tile_size: 8.0,
+        columns: 13,
+        rows: 8,
+    },
+    SheetConfig {
+        name: "nightborne",
+        path: "NightBorne.png",
+        tile_size: 80.0,
+        columns: 23,
+        rows: 5,
+    },
+];
+
+/// Data files and shaders that have to be there before the game starts
+const PRELOAD: &[&str] = &[
+    "enemies.ai.ron",
+    "items.items.ron",
+    "nightborne.boss.ron",
+    "shaders/light_mask.wgsl",
+];
+
+const PROJECT_PATH: &str = "gamejam.ldtk";
+
+/// Texture atlases from `SPRITE_SHEETS`, by name
+#[derive(Resource, Default)]
+pub struct SpriteSheets(HashMap<&'static str, Handle<TextureAtlas>>);
+
+impl SpriteSheets {
+    pub fn get(&self, name: &str) -> Handle<TextureAtlas> {
+        self.0.get(name).cloned().unwrap_or_default()
+    }
+}
+
+/// Everything being loaded, by path
+#[derive(Resource)]
+pub struct LoadingAssets {
+    pub ldtk_project: Handle<LdtkProject>,
+    handles: Vec<(&'static str, UntypedHandle)>,
+}
+
+/// Paths that failed to load, shown on the error screen
+#[derive(Resource, Default)]
+struct LoadErrors(Vec<&'static str>);
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingProgress;
+
+#[derive(Component)]
+struct LoadingBar;
+
+#[derive(Component)]
+struct LoadErrorScreen;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadErrors>()
+            .add_systems(Startup, start_loading)
+            .add_systems(OnEnter(GameState::Loading), spawn_loading_screen)
+            .add_systems(Update, check_loading.run_if(in_state(GameState::Loading)))
+            .add_systems(OnExit(GameState::Loading), despawn_loading_screen)
+            .add_systems(OnEnter(GameState::LoadFailed), spawn_load_error_screen);
+    }
+}
+
+fn start_loading(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let ldtk_project: Handle<LdtkProject> = asset_server.load(PROJECT_PATH);
+
+    let mut handles = vec![(PROJECT_PATH, ldtk_project.clone().untyped())];
+    let mut sprite_sheets = SpriteSheets::default();
+
+    for sheet in SPRITE_SHEETS {
+        let texture: Handle<Image> = asset_server.load(sheet.path);
+
+        handles.push((sheet.path, texture.clone().untyped()));
+
+        let atlas = TextureAtlas::from_grid(
+            texture,
+            Vec2::splat(sheet.tile_size),
+            sheet.columns,
+            sheet.rows,
+            None,
+            None,
+        );
+
+        sprite_sheets
+            .0
+            .insert(sheet.name, texture_atlases.add(atlas));
+    }
+
+    for path in PRELOAD {
+        handles.push((*path, asset_server.load_untyped(*path).untyped()));
+    }
+
+    commands.insert_resource(sprite_sheets);
+    commands.insert_resource(LoadingAssets {
+        ldtk_project,
+        handles,
+    });
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            LoadingScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.0),
+                    ..Default::default()
+                },
+                background_color: Color::BLACK.into(),
+                // Over the HUD, which is already there
+                z_index: ZIndex::Global(10),
+                ..Default::default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                LoadingProgress,
+                TextBundle::from_section(
+                    "Loading...",
+                    TextStyle {
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ),
+            ));
+
+            screen
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(240.0),
+                        height: Val::Px(12.0),
+                        ..Default::default()
+                    },
+                    background_color: Color::DARK_GRAY.into(),
+                    ..Default::default()
+                })
+                .with_children(|bar| {
+                    bar.spawn((
+                        LoadingBar,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..Default::default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..Default::default()
+                        },
+                    ));
+                });
+        });
+}
+
+/// Moves on to the game once everything is in, or to the error screen if anything failed
+fn check_loading(
+    loading: Res<LoadingAssets>,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<LoadErrors>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut progress: Query<&mut Text, With<LoadingProgress>>,
+    mut bar: Query<&mut Style, With<LoadingBar>>,
+) {
+    let mut loaded = 0;
+
+    for (path, handle) in &loading.handles {
+        match asset_server.get_recursive_dependency_load_state(handle.id()) {
+            Some(RecursiveDependencyLoadState::Loaded) => loaded += 1,
+            Some(RecursiveDependencyLoadState::Failed) => {
+                if !errors.0.contains(path) {
+                    error!("Failed to load {path}");
+                    errors.0.push(*path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !errors.0.is_empty() {
+        next_state.set(GameState::LoadFailed);
+        return;
+    }
+
+    let total = loading.handles.len();
+
+    for mut text in progress.iter_mut() {
+        text.sections[0].value = format!("Loading... {loaded}/{total}");
+    }
+
+    for mut style in bar.iter_mut() {
+        style.width = Val::Percent(100.0 * loaded as f32 / total.max(1) as f32);
+    }
+
+    if loaded == total {
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn despawn_loading_screen(mut commands: Commands, screens: Query<Entity, With<LoadingScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_load_error_screen(mut commands: Commands, errors: Res<LoadErrors>) {
+    commands
+        .spawn((
+            LoadErrorScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.0),
+                    ..Default::default()
+                },
+                background_color: Color::BLACK.into(),
+                z_index: ZIndex::Global(10),
+                ..Default::default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn(TextBundle::from_section(
+                "The game couldn't load",
+                TextStyle {
+                    font_size: 32.0,
+                    color: Color::RED,
+                    ..Default::default()
+                },
+            ));
+
+            for path in &errors.0 {
+                screen.spawn(TextBundle::from_section(
+                    format!("Missing or broken: assets/{path}"),
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ));
+            }
+        });
+}
@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    pickups::{Pickup, PickupBundle},
+    EnemyDied,
+};
+
+/// A single entry of a drop table
+#[derive(Clone)]
+pub struct LootDrop {
+    pub pickup: Pickup,
+    /// Between 0.0 (never) and 1.0 (always)
+    pub chance: f32,
+}
+
+/// What an enemy can leave behind when it dies
+/// Every entry is rolled on its own, so an enemy can drop several pickups at once
+#[derive(Default, Component, Clone)]
+pub struct DropTable {
+    pub drops: Vec<LootDrop>,
+}
+
+pub struct LootPlugin;
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, spawn_drops);
+    }
+}
+
+fn spawn_drops(
+    mut commands: Commands,
+    mut died: EventReader<EnemyDied>,
+    drop_tables: Query<&DropTable>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for event in died.read() {
+        let Ok(drop_table) = drop_tables.get(event.enemy) else {
+            continue;
+        };
+
+        let rolled = drop_table
+            .drops
+            .iter()
+            .filter(|drop| rng.gen::<f32>() < drop.chance);
+
+        for (i, drop) in rolled.enumerate() {
+            // Spread the drops out a bit so they don't stack on top of each other
+            let offset = Vec3::new(i as f32 * 7.0, 0.0, 1.0);
+
+            commands.entity(event.parent).with_children(|parent| {
+                parent.spawn(PickupBundle::new(
+                    drop.pickup.clone(),
+                    event.translation + offset,
+                ));
+            });
+        }
+    }
+}
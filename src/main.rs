@@ -1,3 +1,28 @@
+mod ai;
+mod boss;
+mod collision;
+mod combat;
+mod day_night;
+mod defense;
+mod hazards;
+mod hud;
+mod interactions;
+mod inventory;
+mod leveling;
+mod lighting;
+mod loading;
+mod loot;
+mod navigation;
+mod parallax;
+mod particles;
+mod perception;
+mod pickups;
+mod platforms;
+mod projectiles;
+mod ron_asset;
+mod save;
+mod spawners;
+
 use std::time::Duration;
 
 use bevy::{
@@ -12,6 +37,29 @@ use bevy_rapier2d::{
     plugin::{NoUserData, RapierPhysicsPlugin},
     prelude::*,
 };
+use ai::{AiArchetype, AiPlugin, AiState};
+use boss::BossPlugin;
+use collision::Layer;
+use combat::{CombatPlugin, Hit, HitStun, Knockback};
+use day_night::DayNightPlugin;
+use defense::{Blocking, Defense, DefensePlugin, STAGGER};
+use hazards::{DebufTimers, HazardsPlugin};
+use hud::HudPlugin;
+use interactions::InteractionsPlugin;
+use inventory::{Equipment, EquipmentStats, Inventory, InventoryPlugin};
+use leveling::{ExperienceReward, LevelingPlugin};
+use lighting::{Light2d, LightingPlugin};
+use loading::{LoadingAssets, LoadingPlugin};
+use loot::{DropTable, LootDrop, LootPlugin};
+use navigation::{NavAgent, NavGraph, NavGraphs, NavigationPlugin};
+use parallax::ParallaxPlugin;
+use particles::{Burst, Effect, ParticlesPlugin};
+use perception::{Awareness, Noise, Perception, PerceptionPlugin};
+use pickups::{Keys, Pickup, PickupsPlugin};
+use platforms::PlatformsPlugin;
+use projectiles::ProjectilesPlugin;
+use save::SavePlugin;
+use spawners::SpawnersPlugin;
 
 fn main() {
     App::new()
@@ -19,8 +67,43 @@ fn main() {
         .add_plugins(LdtkPlugin)
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(70.0))
         .add_plugins(RapierDebugRenderPlugin::default())
+        .add_plugins(LootPlugin)
+        .add_plugins(PickupsPlugin)
+        .add_plugins(InventoryPlugin)
+        .add_plugins(LevelingPlugin)
+        .add_plugins(SavePlugin)
+        .add_plugins(HudPlugin)
+        .add_plugins(ProjectilesPlugin)
+        .add_plugins(BossPlugin)
+        .add_plugins(AiPlugin)
+        .add_plugins(NavigationPlugin)
+        .add_plugins(PerceptionPlugin)
+        .add_plugins(SpawnersPlugin)
+        .add_plugins(CombatPlugin)
+        .add_plugins(DefensePlugin)
+        .add_plugins(PlatformsPlugin)
+        .add_plugins(InteractionsPlugin)
+        .add_plugins(HazardsPlugin)
+        .add_plugins(DayNightPlugin)
+        .add_plugins(ParallaxPlugin)
+        .add_plugins(LightingPlugin)
+        .add_plugins(ParticlesPlugin)
+        .add_plugins(LoadingPlugin)
+        .add_state::<GameState>()
         .insert_resource(LevelSelection::index(0))
+        .add_event::<EnemyDied>()
         .add_systems(Startup, init)
+        .add_systems(
+            OnTransition {
+                from: GameState::Loading,
+                to: GameState::Playing,
+            },
+            spawn_world,
+        )
+        .add_systems(Update, toggle_pause)
+        .add_systems(OnEnter(GameState::Paused), pause_physics)
+        .add_systems(OnExit(GameState::Paused), resume_physics)
+        .add_systems(OnEnter(GameState::Victory), pause_physics)
         .add_systems(
             Update,
             (
@@ -30,23 +113,40 @@ fn main() {
                 handle_velocity,
                 animate,
                 recover_stamina,
+                update_max_stats,
+                check_grounded,
+                check_walls,
+                end_dash,
                 enemy_attack,
                 check_health,
                 player_attack,
-                check_enemy_health
-            ),
+                check_enemy_health,
+                animate_enemy_state,
+            )
+                .run_if(in_state(GameState::Playing)),
         )
         .register_ldtk_entity::<PlayerBundle>("Player")
         .register_ldtk_entity::<Enemy1Bundle>("Enemy")
-<<<<<<< HEAD
         .register_ldtk_entity::<Enemy2Bundle>("Enemy2")
-        .register_ldtk_int_cell::<WallBundle>(1)
-=======
-        .register_ldtk_int_cell::<WallBundle>(0)
->>>>>>> a5ba3f1f514c3cf0089aebefca9758069534dd11
+        .register_ldtk_int_cell_for_layer::<WallBundle>("The_dark_side", WALL)
+        .register_ldtk_int_cell_for_layer::<WallBundle>("The_light_side", WALL)
         .run();
 }
 
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum GameState {
+    /// Waiting on the LDtk project, sprite sheets and data files
+    #[default]
+    Loading,
+    /// Something didn't load, the error screen is up
+    LoadFailed,
+    Playing,
+    /// Gameplay is frozen and the inventory screen is open
+    Paused,
+    /// The final boss is dead, the game is over
+    Victory,
+}
+
 //bro
 #[derive(Component)]
 struct AnimationTimer {
@@ -62,11 +162,26 @@ impl Default for AnimationTimer {
 }
 
 #[derive(Component)]
-struct Health(i64);
+struct Health {
+    current: i64,
+    max: i64,
+}
 
 impl Default for Health {
     fn default() -> Self {
-        Self(100)
+        Self {
+            current: Self::BASE,
+            max: Self::BASE,
+        }
+    }
+}
+
+impl Health {
+    /// Max health before any level-ups or equipment
+    const BASE: i64 = 100;
+
+    fn heal(&mut self, amount: i64) {
+        self.current = (self.current + amount).min(self.max);
     }
 }
 
@@ -77,57 +192,119 @@ struct PlayerBulletFire{
 }
 
 
-#[derive(Default, Component)]
+#[derive(Default, Component, Clone, Copy)]
 enum BulletType{
     #[default]
     Enemy,
     Player,
 }
 
-#[derive(Default, Component)]
-struct Bullet;
+#[derive(Component)]
+struct Bullet {
+    damage: i64,
+    // Bullets that never hit anything despawn when this runs out
+    lifetime: Timer,
+}
+
+impl Default for Bullet {
+    fn default() -> Self {
+        Self {
+            damage: 10,
+            lifetime: Timer::new(Duration::from_secs(3), TimerMode::Once),
+        }
+    }
+}
 
 #[derive(Bundle, LdtkEntity)]
 struct BulletBundle{
     bullet: Bullet,
-    #[sprite_sheet_bundle]
-    sprite_sheet_bundle: SpriteSheetBundle,
+    #[sprite_bundle]
+    sprite_bundle: SpriteBundle,
     collider: Collider,
     velocity: Velocity,
     bullet_type: BulletType,
+    rigid_body: RigidBody,
+    sensor: Sensor,
+    active_collision_types: ActiveCollisionTypes,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
 }
 
 impl Default for BulletBundle {
     fn default() -> Self {
         Self {
             bullet: Bullet::default(),
-            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            sprite_bundle: SpriteBundle::default(),
             collider: Collider::ball(0.4),
             velocity: Velocity::zero(),
             bullet_type: BulletType::default(),
+            rigid_body: RigidBody::KinematicVelocityBased,
+            sensor: Sensor,
+            // Kinematic bodies don't see fixed walls by default
+            active_collision_types: ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC,
+            collision_group: Layer::EnemyProjectile.collision_groups(),
+            solver_group: Layer::EnemyProjectile.solver_groups(),
         }
     }
 }
 
-<<<<<<< HEAD
+impl BulletBundle {
+    fn new(bullet_type: BulletType, damage: i64, translation: Vec3, linvel: Vec2) -> Self {
+        let (layer, color) = match bullet_type {
+            BulletType::Enemy => (Layer::EnemyProjectile, Color::ORANGE_RED),
+            BulletType::Player => (Layer::PlayerProjectile, Color::CYAN),
+        };
+
+        Self {
+            bullet: Bullet {
+                damage,
+                ..Default::default()
+            },
+            sprite_bundle: SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(3.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(translation),
+                ..Default::default()
+            },
+            collider: Collider::ball(1.5),
+            velocity: Velocity::linear(linvel),
+            bullet_type,
+            collision_group: layer.collision_groups(),
+            solver_group: layer.solver_groups(),
+            ..Default::default()
+        }
+    }
+}
 
-=======
 #[derive(Default, Component)]
 struct SpawnBullet{
     premission: bool
 }
 
-#[derive(Default, Component)]
-struct LightSide{
-    light_on: bool
-}
->>>>>>> a5ba3f1f514c3cf0089aebefca9758069534dd11
 #[derive(Component)]
-struct Stamina(i64);
+struct Stamina {
+    current: i64,
+    max: i64,
+}
 
 impl Default for Stamina {
     fn default() -> Self {
-        Self(100)
+        Self {
+            current: Self::BASE,
+            max: Self::BASE,
+        }
+    }
+}
+
+impl Stamina {
+    /// Max stamina before any level-ups or equipment
+    const BASE: i64 = 100;
+
+    fn recover(&mut self, amount: i64) {
+        self.current = (self.current + amount).min(self.max);
     }
 }
 
@@ -136,7 +313,7 @@ struct Debufs {
     debufs: Vec<DebufsEnum>,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
 enum DebufsEnum {
     #[default]
     None,
@@ -151,15 +328,23 @@ enum EnemyAttack {
     Attack,
 }
 
+/// Time between an enemy's hits, restarted by every hit that lands
 #[derive(Component)]
 struct EnemyAttackCooldown {
     timer: Timer,
 }
 
+impl EnemyAttackCooldown {
+    /// Whether the cooldown is over and, for enemies with an AI, their behavior tree wants to attack
+    fn ready(&self, attack: Option<&EnemyAttack>) -> bool {
+        self.timer.finished() && !matches!(attack, Some(EnemyAttack::None))
+    }
+}
+
 impl Default for EnemyAttackCooldown {
     fn default() -> Self {
         Self {
-            timer: Timer::new(Duration::from_secs_f32(1.0), TimerMode::Repeating),
+            timer: Timer::new(Duration::from_secs_f32(1.0), TimerMode::Once),
         }
     }
 }
@@ -171,6 +356,35 @@ struct EnemyDamage(u64);
 struct EnemyHealth(i64);
 
 
+/// Where an enemy is in its life
+#[derive(Default, Component)]
+enum EnemyState {
+    #[default]
+    Alive,
+    /// Took damage, flashes until the timer runs out
+    Hurt(Timer),
+    /// Playing the death animation
+    Dying,
+    /// Lying on the ground until the timer runs out, then gets despawned
+    Dead(Timer),
+}
+
+/// Sprite sheet frames an enemy goes through when it dies
+#[derive(Component, Clone)]
+struct DeathAnimation {
+    first: usize,
+    frames: usize,
+}
+
+/// Sent once when an enemy's health runs out
+#[derive(Event)]
+struct EnemyDied {
+    enemy: Entity,
+    // The entity the enemy is a child of, along with its position relative to it
+    parent: Entity,
+    translation: Vec3,
+}
+
 #[derive(Default, Component)]
 struct Enemy1;
 
@@ -186,10 +400,24 @@ struct Enemy1Bundle {
     rigid_body: RigidBody,
     lock_axes: LockedAxes,
     collision_group: CollisionGroups,
+    solver_group: SolverGroups,
     attack: EnemyAttack,
     damage: EnemyDamage,
     health: EnemyHealth,
     enemy_attack_cooldown: EnemyAttackCooldown,
+    state: EnemyState,
+    death_animation: DeathAnimation,
+    animation_timer: AnimationTimer,
+    drop_table: DropTable,
+    experience_reward: ExperienceReward,
+    velocity: Velocity,
+    read_mass: ReadMassProperties,
+    ai_archetype: AiArchetype,
+    ai_state: AiState,
+    nav_agent: NavAgent,
+    perception: Perception,
+    awareness: Awareness,
+    knockback: Knockback,
 }
 
 impl Default for Enemy1Bundle {
@@ -202,16 +430,45 @@ impl Default for Enemy1Bundle {
             bullet_type: BulletType::Enemy,
             rigid_body: RigidBody::Dynamic,
             lock_axes: LockedAxes::ROTATION_LOCKED,
-            collision_group: CollisionGroups::new(
-                Group::from_bits(0b10).unwrap(),
-                Group::from_bits(0b1).unwrap(),
-            ),
+            collision_group: Layer::Enemy.collision_groups(),
+            solver_group: Layer::Enemy.solver_groups(),
             attack: EnemyAttack::default(),
             damage: EnemyDamage(45),
             health: EnemyHealth(200),
             enemy_attack_cooldown: EnemyAttackCooldown {
-                timer: Timer::new(Duration::from_secs(1), TimerMode::Repeating),
+                timer: Timer::new(Duration::from_secs(1), TimerMode::Once),
+            },
+            state: EnemyState::default(),
+            death_animation: DeathAnimation {
+                first: 27,
+                frames: 9,
             },
+            animation_timer: AnimationTimer::default(),
+            drop_table: DropTable {
+                drops: vec![
+                    LootDrop {
+                        pickup: Pickup::Health(40),
+                        chance: 0.75,
+                    },
+                    LootDrop {
+                        pickup: Pickup::Stamina(50),
+                        chance: 0.5,
+                    },
+                ],
+            },
+            experience_reward: ExperienceReward(60),
+            velocity: Velocity::zero(),
+            read_mass: ReadMassProperties::default(),
+            ai_archetype: AiArchetype("satyr"),
+            ai_state: AiState::default(),
+            nav_agent: NavAgent::default(),
+            perception: Perception {
+                vision_range: 90.0,
+                vision_angle: 60.0,
+                hearing_radius: 70.0,
+            },
+            awareness: Awareness::default(),
+            knockback: Knockback::default(),
         }
     }
 }
@@ -231,10 +488,24 @@ struct Enemy2Bundle {
     rigid_body: RigidBody,
     lock_axes: LockedAxes,
     collision_group: CollisionGroups,
+    solver_group: SolverGroups,
     attack: EnemyAttack,
     damage: EnemyDamage,
     health: EnemyHealth,
     enemy_attack_cooldown: EnemyAttackCooldown,
+    state: EnemyState,
+    death_animation: DeathAnimation,
+    animation_timer: AnimationTimer,
+    drop_table: DropTable,
+    experience_reward: ExperienceReward,
+    velocity: Velocity,
+    read_mass: ReadMassProperties,
+    ai_archetype: AiArchetype,
+    ai_state: AiState,
+    nav_agent: NavAgent,
+    perception: Perception,
+    awareness: Awareness,
+    knockback: Knockback,
 }
 
 impl Default for Enemy2Bundle {
@@ -247,16 +518,40 @@ impl Default for Enemy2Bundle {
             bullet_type: BulletType::Enemy,
             rigid_body: RigidBody::Dynamic,
             lock_axes: LockedAxes::ROTATION_LOCKED,
-            collision_group: CollisionGroups::new(
-                Group::from_bits(0b10).unwrap(),
-                Group::from_bits(0b1).unwrap(),
-            ),
+            collision_group: Layer::Enemy.collision_groups(),
+            solver_group: Layer::Enemy.solver_groups(),
             attack: EnemyAttack::default(),
             damage: EnemyDamage(10),
             health: EnemyHealth(70),
             enemy_attack_cooldown: EnemyAttackCooldown {
-                timer: Timer::new(Duration::from_secs_f32(0.5), TimerMode::Repeating),
+                timer: Timer::new(Duration::from_secs_f32(0.5), TimerMode::Once),
             },
+            state: EnemyState::default(),
+            death_animation: DeathAnimation {
+                first: 12,
+                frames: 6,
+            },
+            animation_timer: AnimationTimer::default(),
+            drop_table: DropTable {
+                drops: vec![LootDrop {
+                    pickup: Pickup::Health(15),
+                    chance: 0.25,
+                }],
+            },
+            experience_reward: ExperienceReward(20),
+            velocity: Velocity::zero(),
+            read_mass: ReadMassProperties::default(),
+            ai_archetype: AiArchetype("lizard"),
+            ai_state: AiState::default(),
+            nav_agent: NavAgent::default(),
+            // Short-sighted but jumpy
+            perception: Perception {
+                vision_range: 60.0,
+                vision_angle: 45.0,
+                hearing_radius: 100.0,
+            },
+            awareness: Awareness::default(),
+            knockback: Knockback::default(),
         }
     }
 }
@@ -268,6 +563,8 @@ enum Animation {
     Dash(u8),
     Attack(u8),
     ChargedAttack(u8),
+    /// Knocked back by a hit, lasts as long as the hit-stun
+    Hurt(u8),
     Dead,
 
 }
@@ -292,6 +589,60 @@ impl Default for Animation {
     }
 }
 
+/// Dash bookkeeping that lives outside of stamina
+#[derive(Component)]
+struct Dash {
+    cooldown: Timer,
+    air_dash_available: bool,
+    // Collision filters to restore once the dash ends
+    restore_filters: Option<Group>,
+}
+
+impl Default for Dash {
+    fn default() -> Self {
+        let mut cooldown = Timer::new(Duration::from_secs_f32(0.6), TimerMode::Once);
+
+        // The first dash shouldn't have to wait for the cooldown
+        cooldown.tick(cooldown.duration());
+
+        Self {
+            cooldown,
+            air_dash_available: true,
+            restore_filters: None,
+        }
+    }
+}
+
+#[derive(Default, Component)]
+struct Grounded(bool);
+
+/// Wall slide and wall jump bookkeeping
+#[derive(Component)]
+struct WallSlide {
+    /// Which side a wall is touching the player on, -1.0 for left and 1.0 for right
+    side: Option<f32>,
+    /// Steering is ignored until this runs out so it can't cancel a wall jump's kick right away
+    kick: Timer,
+}
+
+impl Default for WallSlide {
+    fn default() -> Self {
+        let mut kick = Timer::from_seconds(0.2, TimerMode::Once);
+        kick.tick(kick.duration());
+
+        Self { side: None, kick }
+    }
+}
+
+impl WallSlide {
+    /// Fastest the player falls while sliding down a wall
+    const MAX_SPEED: f32 = 30.0;
+}
+
+/// Entities with this component don't take damage
+#[derive(Component)]
+struct Invulnerable;
+
 #[derive(Default, Component)]
 enum PlayerAttack {
     #[default]
@@ -299,13 +650,50 @@ enum PlayerAttack {
     Attack,
     ChargedAttack,
 }
-#[derive(Default, Component)]
+#[derive(Component)]
 struct Level{
-    level: u8
+    level: u8,
+    // Experience gathered towards the next level
+    experience: u64,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            experience: 0,
+        }
+    }
+}
+
+impl Level {
+    const HEALTH_PER_LEVEL: i64 = 10;
+    const STAMINA_PER_LEVEL: i64 = 5;
+    const DAMAGE_PER_LEVEL: i64 = 3;
+
+    fn levels_gained(&self) -> i64 {
+        self.level.saturating_sub(1) as i64
+    }
+
+    fn bonus_health(&self) -> i64 {
+        self.levels_gained() * Self::HEALTH_PER_LEVEL
+    }
+
+    fn bonus_stamina(&self) -> i64 {
+        self.levels_gained() * Self::STAMINA_PER_LEVEL
+    }
+
+    fn bonus_damage(&self) -> i64 {
+        self.levels_gained() * Self::DAMAGE_PER_LEVEL
+    }
 }
 #[derive(Default, Component)]
 struct Player;
 
+/// The camera that follows the player, as opposed to the ones drawing into textures
+#[derive(Component)]
+struct MainCamera;
+
 #[derive(Bundle, LdtkEntity)]
 struct PlayerBundle {
     player: Player,
@@ -314,6 +702,7 @@ struct PlayerBundle {
     #[grid_coords]
     grid_coords: GridCoords,
     debufs: Debufs,
+    debuf_timers: DebufTimers,
     health: Health,
     stamina: Stamina,
     collider: Collider,
@@ -323,14 +712,25 @@ struct PlayerBundle {
     ccd: Ccd,
     damping: Damping,
     mass: ColliderMassProperties,
+    read_mass: ReadMassProperties,
     bouncyness: Restitution,
     animation_timer: AnimationTimer,
     bullet_type: BulletType,
     collision_group: CollisionGroups,
+    solver_group: SolverGroups,
     animation: Animation,
     attack: PlayerAttack,
     stamina_recovery: StaminaRecoveryTimer,
     level: Level,
+    dash: Dash,
+    grounded: Grounded,
+    wall_slide: WallSlide,
+    keys: Keys,
+    inventory: Inventory,
+    equipment: Equipment,
+    equipment_stats: EquipmentStats,
+    knockback: Knockback,
+    light: Light2d,
 }
 
 impl Default for PlayerBundle {
@@ -340,6 +740,7 @@ impl Default for PlayerBundle {
             sprite_sheet_bundle: SpriteSheetBundle::default(),
             grid_coords: GridCoords::default(),
             debufs: Debufs::default(),
+            debuf_timers: DebufTimers::default(),
             health: Health::default(),
             stamina: Stamina::default(),
             collider: Collider::cuboid(25.0, 25.0),
@@ -349,54 +750,89 @@ impl Default for PlayerBundle {
             ccd: Ccd::enabled(),
             damping: Damping::default(),
             mass: ColliderMassProperties::Density(100.0),
+            read_mass: ReadMassProperties::default(),
             bouncyness: Restitution {
                 coefficient: 0.0,
                 combine_rule: CoefficientCombineRule::Min,
             },
             bullet_type: BulletType::Player,
             animation_timer: AnimationTimer::default(),
-            collision_group: CollisionGroups::new(
-                Group::from_bits(0b10).unwrap(),
-                Group::from_bits(0b1).unwrap(),
-            ),
+            collision_group: Layer::Player.collision_groups(),
+            solver_group: Layer::Player.solver_groups(),
             animation: Animation::default(),
             attack: PlayerAttack::default(),
             stamina_recovery: StaminaRecoveryTimer::default(),
             level: Level::default(),
+            dash: Dash::default(),
+            grounded: Grounded::default(),
+            wall_slide: WallSlide::default(),
+            keys: Keys::default(),
+            inventory: Inventory::default(),
+            equipment: Equipment::default(),
+            equipment_stats: EquipmentStats::default(),
+            knockback: Knockback::default(),
+            light: Light2d::default(),
         }
     }
 }
 
+/// IntGrid value of walls in the side layers, the background uses the same value for scenery
+const WALL: i32 = 1;
+
 #[derive(Default, Component)]
 struct Wall;
 
+/// One of the merged colliders built from a level's walls
+#[derive(Component)]
+struct WallCollider;
+
 #[derive(Default, Bundle, LdtkIntCell)]
 struct WallBundle {
     wall: Wall,
 }
 
 
+/// Half the primary window's size, everything is laid out around it
+/// Falls back to the origin if there's no window, so the game still runs headless
+fn window_center(window: &Query<&Window, With<PrimaryWindow>>) -> Vec2 {
+    match window.get_single() {
+        Ok(window) => Vec2::new(window.width(), window.height()) / 2.0,
+        Err(error) => {
+            warn!(%error, "No primary window, centering the view on the origin");
+            Vec2::ZERO
+        }
+    }
+}
+
 fn init(
     window: Query<&Window, With<PrimaryWindow>>,
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
 ) {
-    let window = window.get_single().unwrap();
+    let center = window_center(&window);
 
     let mut camera = Camera2dBundle::default();
 
     camera.projection.scale = 0.2;
 
-    camera.transform.translation.x = window.width() / 2.0;
-    camera.transform.translation.y = window.height() / 2.0;
+    camera.transform.translation.x = center.x;
+    camera.transform.translation.y = center.y;
 
-    commands.spawn(camera);
+    commands.spawn((camera, MainCamera));
+}
+
+/// The world only goes in once the project is loaded, so levels spawn with everything they need
+fn spawn_world(
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut commands: Commands,
+    loading: Res<LoadingAssets>,
+) {
+    let center = window_center(&window);
 
     commands.spawn(LdtkWorldBundle {
-        ldtk_handle: asset_server.load("gamejam.ldtk"),
+        ldtk_handle: loading.ldtk_project.clone(),
         transform: Transform::from_xyz(
-            window.width() / 2.0 - 256.0 / 2.0,
-            window.height() / 2.0 - 256.0 / 2.0,
+            center.x - 256.0 / 2.0,
+            center.y - 256.0 / 2.0,
             0.0,
         ),
         ..Default::default()
@@ -404,16 +840,55 @@ fn init(
 }
 
 fn handle_input(
-    mut player: Query<(&mut Stamina, &mut Velocity, &mut Animation, &TextureAtlasSprite, &mut PlayerAttack), With<Player>>,
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &mut Stamina,
+            &mut Velocity,
+            &mut Animation,
+            &TextureAtlasSprite,
+            &mut PlayerAttack,
+            &mut Dash,
+            &Grounded,
+            &mut CollisionGroups,
+            &EquipmentStats,
+            &GlobalTransform,
+            &mut WallSlide,
+        ),
+        // No input while reeling from a hit or holding up a guard
+        (With<Player>, Without<HitStun>, Without<Blocking>),
+    >,
     keyb: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut noise: EventWriter<Noise>,
+    mut bursts: EventWriter<Burst>,
 ) {
-    if let Ok((mut stamina, mut velocity, mut animation, sprite, mut attack)) = player.get_single_mut() {
+    if let Ok((entity, mut stamina, mut velocity, mut animation, sprite, mut attack, mut dash, grounded, mut collision_groups, stats, global_transform, mut wall_slide)) =
+        player.get_single_mut()
+    {
+        dash.cooldown.tick(time.delta());
+
+        let dash_cost = (25 + stats.0.stamina_cost).max(0);
+        let attack_cost = (10 + stats.0.stamina_cost).max(0);
+        let charged_attack_cost = (75 + stats.0.stamina_cost).max(0);
+
+        let position = global_transform.translation().truncate();
+
         if let Animation::Run(_) = *animation {
             if keyb.just_pressed(KeyCode::Space) {
-                if stamina.0 < 25 {
+                if stamina.current < dash_cost || !dash.cooldown.finished() {
                     return;
                 }
 
+                if !grounded.0 {
+                    if !dash.air_dash_available {
+                        return;
+                    }
+
+                    dash.air_dash_available = false;
+                }
+
                 if sprite.flip_x {
                     velocity.linvel = Vec2::new(-140.0, 0.0);
                 } else {
@@ -422,27 +897,65 @@ fn handle_input(
 
                 *animation = Animation::Dash(0);
 
-                stamina.0 -= 25;
+                // Pass through enemies and ignore their hits until the dash is over
+                dash.restore_filters = Some(collision_groups.filters);
+                collision_groups.filters.remove(Layer::Enemy.group());
+                commands.entity(entity).insert(Invulnerable);
+
+                dash.cooldown.reset();
+
+                stamina.current -= dash_cost;
+
+                bursts.send(Burst {
+                    position,
+                    effect: Effect::Dust,
+                    direction: velocity.linvel.x.signum(),
+                });
+
+                noise.send(Noise {
+                    position,
+                    loudness: 1.0,
+                });
             } else if keyb.just_pressed(KeyCode::F) {
-                if stamina.0 < 10 {
+                if stamina.current < attack_cost {
                     return;
                 }
 
                 *animation = Animation::Attack(0);
                 *attack = PlayerAttack::Attack;
 
-                stamina.0 -= 10;
+                stamina.current -= attack_cost;
+
+                noise.send(Noise {
+                    position,
+                    loudness: 1.0,
+                });
             } else if keyb.just_pressed(KeyCode::G) {
-                if stamina.0 < 75 {
+                if stamina.current < charged_attack_cost {
                     return;
                 }
 
                 *animation = Animation::ChargedAttack(0);
                 *attack = PlayerAttack::ChargedAttack;
 
-                stamina.0 -= 75;
+                stamina.current -= charged_attack_cost;
+
+                noise.send(Noise {
+                    position,
+                    loudness: 1.5,
+                });
             } else if keyb.just_pressed(KeyCode::W) {
-                velocity.linvel += Vec2::new(0.0, 50.0);
+                match wall_slide.side {
+                    // Kick off away from the wall
+                    Some(side) if !grounded.0 => {
+                        velocity.linvel = Vec2::new(-side * 70.0, 80.0);
+                        wall_slide.kick.reset();
+                        dash.air_dash_available = true;
+                    }
+                    _ => velocity.linvel += Vec2::new(0.0, 50.0),
+                }
+            } else if !wall_slide.kick.finished() {
+                // Let the wall jump carry the player before steering kicks in again
             } else if keyb.pressed(KeyCode::D) {
                 let y = velocity.linvel.y;
 
@@ -481,8 +994,8 @@ fn handle_velocity(
 
 fn camera_follow_player(
     window: Query<&Window, With<PrimaryWindow>>,
-    mut camera: Query<&mut Transform, (With<Camera>, Without<Player>)>,
-    player: Query<&Transform, (With<Player>, Without<Camera>)>,
+    mut camera: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
+    player: Query<&Transform, (With<Player>, Without<MainCamera>)>,
 ) {
     if let Ok(mut cam_transform) = camera.get_single_mut() {
         if let Ok(player_transform) = player.get_single() {
@@ -497,13 +1010,23 @@ fn camera_follow_player(
     }
 }
 
+/// Low enough that pushing into a wall doesn't hold the player up against gravity, but walls
+/// double as floors so it still has to bring a sliding player to a stop
+const WALL_FRICTION: Friction = Friction {
+    coefficient: 0.1,
+    combine_rule: CoefficientCombineRule::Min,
+};
+
+/// Rebuilds the colliders and nav graph of every level that got new walls, including levels
+/// respawned by LDtk or by a hot-reload of the project, dropping whatever was built before
 fn spawn_wall_collision(
     mut commands: Commands,
-    wall_query: Query<(&GridCoords, &Parent), Added<Wall>>,
-    parent_query: Query<&Parent, Without<Wall>>,
-    level_query: Query<(Entity, &LevelIid)>,
-    ldtk_projects: Query<&Handle<LdtkProject>>,
-    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    new_walls: Query<&Parent, Added<Wall>>,
+    new_levels: Query<Entity, Added<LevelIid>>,
+    wall_query: Query<(&GridCoords, &Parent), With<Wall>>,
+    layer_query: Query<(&LayerMetadata, &Parent), Without<Wall>>,
+    collider_query: Query<(Entity, &Parent), With<WallCollider>>,
+    mut nav_graphs: ResMut<NavGraphs>,
 ) {
     /// Represents a wide wall that is 1 tile tall
     /// Used to spawn wall collisions
@@ -528,134 +1051,187 @@ fn spawn_wall_collision(
     // This has two consequences in the resulting collision entities:
     // 1. it forces the walls to be split along level boundaries
     // 2. it lets us easily add the collision entities as children of the appropriate level entity
-    let mut level_to_wall_locations: HashMap<Entity, HashSet<GridCoords>> = HashMap::new();
+    //
+    // The layer the walls are in also tells us the size of the level's grid, so nothing has to be
+    // looked up in the project
+    let mut level_to_wall_locations: HashMap<Entity, (&LayerMetadata, HashSet<GridCoords>)> =
+        HashMap::new();
+
+    // Levels are rebuilt whole, so walls that were already there are merged with the new ones
+    // A level that spawned without any walls is in here too, so it loses what it had before
+    let mut dirty_levels: HashSet<Entity> = new_levels.iter().collect();
+
+    dirty_levels.extend(
+        new_walls
+            .iter()
+            .filter_map(|parent| layer_query.get(parent.get()).ok())
+            .map(|(_, level)| level.get()),
+    );
+
+    if dirty_levels.is_empty() {
+        return;
+    }
+
+    for (collider, level) in collider_query.iter() {
+        if dirty_levels.contains(&level.get()) {
+            commands.entity(collider).despawn_recursive();
+        }
+    }
+
+    for level in &dirty_levels {
+        nav_graphs.0.remove(level);
+    }
+
+    let mut mismatched_layers: HashSet<Entity> = HashSet::new();
 
     wall_query.iter().for_each(|(&grid_coords, parent)| {
         // An intgrid tile's direct parent will be a layer entity, not the level entity
         // To get the level entity, you need the tile's grandparent.
-        // This is where parent_query comes in.
-        if let Ok(grandparent) = parent_query.get(parent.get()) {
-            level_to_wall_locations
-                .entry(grandparent.get())
-                .or_default()
-                .insert(grid_coords);
+        // This is where layer_query comes in.
+        let Ok((layer, grandparent)) = layer_query.get(parent.get()) else {
+            warn!(
+                layer = ?parent.get(),
+                "Wall tile isn't in a level's layer, skipping its collision"
+            );
+            return;
+        };
+
+        if !dirty_levels.contains(&grandparent.get()) {
+            return;
+        }
+
+        let (level_layer, level_walls) = level_to_wall_locations
+            .entry(grandparent.get())
+            .or_insert_with(|| (layer, HashSet::new()));
+
+        // Walls from both sides' layers share one grid, which has to be the same in each
+        let same_grid = (level_layer.c_wid, level_layer.c_hei, level_layer.grid_size)
+            == (layer.c_wid, layer.c_hei, layer.grid_size);
+
+        if !same_grid {
+            if mismatched_layers.insert(parent.get()) {
+                warn!(
+                    level = ?grandparent.get(),
+                    layer = %layer.identifier,
+                    other_layer = %level_layer.identifier,
+                    "Wall layers of a level have different grids, skipping this layer's collision"
+                );
+            }
+
+            return;
         }
+
+        level_walls.insert(grid_coords);
     });
 
-    if !wall_query.is_empty() {
-        level_query.iter().for_each(|(level_entity, level_iid)| {
-            if let Some(level_walls) = level_to_wall_locations.get(&level_entity) {
-                let ldtk_project = ldtk_project_assets
-                    .get(ldtk_projects.single())
-                    .expect("Project should be loaded if level has spawned");
-
-                let level = ldtk_project
-                    .as_standalone()
-                    .get_loaded_level_by_iid(&level_iid.to_string())
-                    .expect("Spawned level should exist in LDtk project");
-
-                let LayerInstance {
-                    c_wid: width,
-                    c_hei: height,
-                    grid_size,
-                    ..
-                } = level.layer_instances()[0];
-
-                // combine wall tiles into flat "plates" in each individual row
-                let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
-
-                for y in 0..height {
-                    let mut row_plates: Vec<Plate> = Vec::new();
-                    let mut plate_start = None;
-
-                    // + 1 to the width so the algorithm "terminates" plates that touch the right edge
-                    for x in 0..width + 1 {
-                        match (plate_start, level_walls.contains(&GridCoords { x, y })) {
-                            (Some(s), false) => {
-                                row_plates.push(Plate {
-                                    left: s,
-                                    right: x - 1,
-                                });
-                                plate_start = None;
-                            }
-                            (None, true) => plate_start = Some(x),
-                            _ => (),
-                        }
+    for (level_entity, (layer, level_walls)) in level_to_wall_locations {
+        let LayerMetadata {
+            c_wid: width,
+            c_hei: height,
+            grid_size,
+            ..
+        } = *layer;
+
+        nav_graphs.0.insert(
+            level_entity,
+            NavGraph::build(&level_walls, width, height, grid_size),
+        );
+
+        // combine wall tiles into flat "plates" in each individual row
+        let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
+
+        for y in 0..height {
+            let mut row_plates: Vec<Plate> = Vec::new();
+            let mut plate_start = None;
+
+            // + 1 to the width so the algorithm "terminates" plates that touch the right edge
+            for x in 0..width + 1 {
+                match (plate_start, level_walls.contains(&GridCoords { x, y })) {
+                    (Some(s), false) => {
+                        row_plates.push(Plate {
+                            left: s,
+                            right: x - 1,
+                        });
+                        plate_start = None;
                     }
-
-                    plate_stack.push(row_plates);
+                    (None, true) => plate_start = Some(x),
+                    _ => (),
                 }
+            }
 
-                // combine "plates" into rectangles across multiple rows
-                let mut rect_builder: HashMap<Plate, Rect> = HashMap::new();
-                let mut prev_row: Vec<Plate> = Vec::new();
-                let mut wall_rects: Vec<Rect> = Vec::new();
-
-                // an extra empty row so the algorithm "finishes" the rects that touch the top edge
-                plate_stack.push(Vec::new());
-
-                for (y, current_row) in plate_stack.into_iter().enumerate() {
-                    for prev_plate in &prev_row {
-                        if !current_row.contains(prev_plate) {
-                            // remove the finished rect so that the same plate in the future starts a new rect
-                            if let Some(rect) = rect_builder.remove(prev_plate) {
-                                wall_rects.push(rect);
-                            }
-                        }
-                    }
-                    for plate in &current_row {
-                        rect_builder
-                            .entry(plate.clone())
-                            .and_modify(|e| e.top += 1)
-                            .or_insert(Rect {
-                                bottom: y as i32,
-                                top: y as i32,
-                                left: plate.left,
-                                right: plate.right,
-                            });
+            plate_stack.push(row_plates);
+        }
+
+        // combine "plates" into rectangles across multiple rows
+        let mut rect_builder: HashMap<Plate, Rect> = HashMap::new();
+        let mut prev_row: Vec<Plate> = Vec::new();
+        let mut wall_rects: Vec<Rect> = Vec::new();
+
+        // an extra empty row so the algorithm "finishes" the rects that touch the top edge
+        plate_stack.push(Vec::new());
+
+        for (y, current_row) in plate_stack.into_iter().enumerate() {
+            for prev_plate in &prev_row {
+                if !current_row.contains(prev_plate) {
+                    // remove the finished rect so that the same plate in the future starts a new rect
+                    if let Some(rect) = rect_builder.remove(prev_plate) {
+                        wall_rects.push(rect);
                     }
-                    prev_row = current_row;
                 }
+            }
+            for plate in &current_row {
+                rect_builder
+                    .entry(plate.clone())
+                    .and_modify(|e| e.top += 1)
+                    .or_insert(Rect {
+                        bottom: y as i32,
+                        top: y as i32,
+                        left: plate.left,
+                        right: plate.right,
+                    });
+            }
+            prev_row = current_row;
+        }
 
-                commands.entity(level_entity).with_children(|level| {
-                    // Spawn colliders for every rectangle..
-                    // Making the collider a child of the level serves two purposes:
-                    // 1. Adjusts the transforms to be relative to the level for free
-                    // 2. the colliders will be despawned automatically when levels unload
-                    for wall_rect in wall_rects {
-                        level
-                            .spawn_empty()
-                            .insert(Collider::cuboid(
-                                (wall_rect.right as f32 - wall_rect.left as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                                (wall_rect.top as f32 - wall_rect.bottom as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                            ))
-                            .insert(RigidBody::Fixed)
-                            .insert(Friction::new(1.0))
-                            .insert(Transform::from_xyz(
-                                (wall_rect.left + wall_rect.right + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                0.,
-                            ))
-                            .insert(GlobalTransform::default());
-                    }
-                });
+        commands.entity(level_entity).with_children(|level| {
+            // Spawn colliders for every rectangle..
+            // Making the collider a child of the level serves two purposes:
+            // 1. Adjusts the transforms to be relative to the level for free
+            // 2. the colliders will be despawned automatically when levels unload
+            for wall_rect in wall_rects {
+                level
+                    .spawn(WallCollider)
+                    .insert(Collider::cuboid(
+                        (wall_rect.right as f32 - wall_rect.left as f32 + 1.)
+                            * grid_size as f32
+                            / 2.,
+                        (wall_rect.top as f32 - wall_rect.bottom as f32 + 1.)
+                            * grid_size as f32
+                            / 2.,
+                    ))
+                    .insert(RigidBody::Fixed)
+                    .insert(WALL_FRICTION)
+                    .insert(Layer::World.collision_groups())
+                    .insert(Layer::World.solver_groups())
+                    .insert(Transform::from_xyz(
+                        (wall_rect.left + wall_rect.right + 1) as f32 * grid_size as f32
+                            / 2.,
+                        (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size as f32
+                            / 2.,
+                        0.,
+                    ))
+                    .insert(GlobalTransform::default());
             }
         });
     }
 }
 
 fn animate(
-    mut player: Query<(&mut TextureAtlasSprite, &mut AnimationTimer, &mut Animation, &mut Velocity), With<Player>>,
+    mut player: Query<(&mut TextureAtlasSprite, &mut AnimationTimer, &mut Animation, &mut Velocity, Has<HitStun>), With<Player>>,
     time: Res<Time>,
     keyb: Res<Input<KeyCode>>,
 ) {
-    for (mut sprite, mut timer, mut animation, mut velocity) in player.iter_mut() {
+    for (mut sprite, mut timer, mut animation, mut velocity, stunned) in player.iter_mut() {
         timer.timer.tick(time.delta());
 
         match *animation {
@@ -683,7 +1259,9 @@ fn animate(
 
                     if *phase == 3 {
                         *animation = Animation::Run(0);
-                        velocity.linvel = Vec2::new(0.0, 0.0);
+
+                        // Keep falling/jumping momentum, only bring the dash speed back down to running speed
+                        velocity.linvel.x = velocity.linvel.x.clamp(-45.0, 45.0);
                     }
                 }
             }
@@ -723,6 +1301,141 @@ fn animate(
                     }
                 }
             }
+            Animation::Hurt(ref mut phase) => {
+                if !stunned {
+                    *animation = Animation::Run(0);
+                } else if timer.timer.just_finished() {
+                    // Hold the last frame if the stun outlasts the animation
+                    sprite.index = 69 + *phase as usize;
+                    *phase = (*phase + 1).min(4);
+                }
+            }
+        }
+    }
+}
+
+fn toggle_pause(
+    keyb: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyb.just_pressed(KeyCode::Escape) || keyb.just_pressed(KeyCode::I) {
+        match state.get() {
+            GameState::Playing => next_state.set(GameState::Paused),
+            GameState::Paused => next_state.set(GameState::Playing),
+            GameState::Loading | GameState::LoadFailed | GameState::Victory => {}
+        }
+    }
+}
+
+fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+fn end_dash(
+    mut commands: Commands,
+    mut player: Query<(Entity, &Animation, &mut Dash, &mut CollisionGroups), With<Player>>,
+) {
+    for (entity, animation, mut dash, mut collision_groups) in player.iter_mut() {
+        if let Animation::Dash(_) = *animation {
+            continue;
+        }
+
+        if let Some(filters) = dash.restore_filters.take() {
+            collision_groups.filters = filters;
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+fn check_grounded(
+    mut player: Query<(Entity, &GlobalTransform, &Collider, &mut Grounded, &mut Dash), With<Player>>,
+    rapier_context: Res<RapierContext>,
+    mut bursts: EventWriter<Burst>,
+) {
+    for (entity, transform, collider, mut grounded, mut dash) in player.iter_mut() {
+        let half_height = collider
+            .as_cuboid()
+            .map(|cuboid| cuboid.half_extents().y)
+            .unwrap_or(0.0);
+
+        let filter = QueryFilter::default()
+            .groups(Layer::Player.collision_groups())
+            .exclude_collider(entity)
+            .exclude_sensors();
+
+        let was_grounded = grounded.0;
+
+        grounded.0 = rapier_context
+            .cast_ray(
+                transform.translation().truncate(),
+                Vec2::NEG_Y,
+                half_height + 1.0,
+                true,
+                filter,
+            )
+            .is_some();
+
+        if grounded.0 && !was_grounded {
+            bursts.send(Burst {
+                position: transform.translation().truncate() - Vec2::new(0.0, half_height),
+                effect: Effect::Dust,
+                direction: 0.0,
+            });
+        }
+
+        // Touching the ground gives the air dash back
+        if grounded.0 {
+            dash.air_dash_available = true;
+        }
+    }
+}
+
+/// Looks for walls on either side of the player with thin shape casts and slows falls along them
+fn check_walls(
+    mut player: Query<(Entity, &GlobalTransform, &Collider, &Grounded, &mut WallSlide, &mut Velocity), With<Player>>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    for (entity, transform, collider, grounded, mut wall_slide, mut velocity) in player.iter_mut() {
+        wall_slide.kick.tick(time.delta());
+
+        let Some(half_extents) = collider.as_cuboid().map(|cuboid| cuboid.half_extents()) else {
+            continue;
+        };
+
+        // Shorter than the player so floors and ceilings at the corners don't count as walls
+        let probe = Collider::cuboid(1.0, half_extents.y * 0.8);
+
+        let filter = QueryFilter::default()
+            .groups(CollisionGroups::new(Layer::Player.group(), Layer::World.group()))
+            .exclude_collider(entity)
+            .exclude_sensors();
+
+        wall_slide.side = if grounded.0 {
+            None
+        } else {
+            [-1.0, 1.0].into_iter().find(|&side| {
+                rapier_context
+                    .cast_shape(
+                        transform.translation().truncate(),
+                        0.0,
+                        Vec2::new(side, 0.0),
+                        &probe,
+                        half_extents.x + 1.0,
+                        true,
+                        filter,
+                    )
+                    .is_some()
+            })
+        };
+
+        if wall_slide.side.is_some() {
+            velocity.linvel.y = velocity.linvel.y.max(-WallSlide::MAX_SPEED);
         }
     }
 }
@@ -732,20 +1445,48 @@ fn recover_stamina(mut player: Query<(&mut Stamina, &mut StaminaRecoveryTimer),
         stamina_recovery_timer.timer.tick(time.delta());
 
         if stamina_recovery_timer.timer.just_finished() {
-            stamina.0 += 15;
+            stamina.recover(15);
+        }
+    }
+}
 
-            if stamina.0 > 100 {
-                stamina.0 = 100;
-            }
+fn update_max_stats(
+    mut player: Query<
+        (&Level, &EquipmentStats, &mut Health, &mut Stamina),
+        (With<Player>, Or<(Changed<Level>, Changed<EquipmentStats>)>),
+    >,
+) {
+    for (level, stats, mut health, mut stamina) in player.iter_mut() {
+        let max_health = (Health::BASE + level.bonus_health() + stats.0.max_health).max(1);
+        let max_stamina = (Stamina::BASE + level.bonus_stamina() + stats.0.max_stamina).max(0);
+
+        // Growing the max tops up by the difference, shrinking it only cuts off the excess
+        if max_health > health.max {
+            health.current += max_health - health.max;
+        }
+
+        if max_stamina > stamina.max {
+            stamina.current += max_stamina - stamina.max;
         }
+
+        health.max = max_health;
+        health.current = health.current.min(max_health);
+
+        stamina.max = max_stamina;
+        stamina.current = stamina.current.min(max_stamina);
     }
 }
 
-fn enemy_attack(mut enemy: Query<(&mut EnemyAttackCooldown, &Transform, &EnemyDamage)>, mut player: Query<(&Transform, &mut Health), With<Player>>, time: Res<Time>)  {
-    for (mut enemy_cooldown, transform, damage) in enemy.iter_mut() {
+fn enemy_attack(mut enemy: Query<(Entity, &mut EnemyAttackCooldown, &Transform, &GlobalTransform, &EnemyDamage, Option<&EnemyAttack>, Option<&Knockback>)>, mut player: Query<(Entity, &Transform, &GlobalTransform, &mut Health, &mut Stamina, &EquipmentStats, &TextureAtlasSprite, Option<&Blocking>), (With<Player>, Without<Invulnerable>)>, time: Res<Time>, mut hits: EventWriter<Hit>)  {
+    for (enemy_entity, mut enemy_cooldown, transform, global_transform, damage, attack, knockback) in enemy.iter_mut() {
         enemy_cooldown.timer.tick(time.delta());
 
-        for (player_transform, mut health) in player.iter_mut() {
+        // Enemies with an AI only hit when their behavior tree says so
+        if !enemy_cooldown.ready(attack) {
+            continue;
+        }
+
+        for (player_entity, player_transform, player_global_transform, mut health, mut stamina, stats, sprite, blocking) in player.iter_mut() {
             let enemy_x = transform.translation.x;
             let enemy_y = transform.translation.y;
 
@@ -758,20 +1499,50 @@ fn enemy_attack(mut enemy: Query<(&mut EnemyAttackCooldown, &Transform, &EnemyDa
             let x = x.abs();
             let y = y.abs();
 
-            if x < 20.0 && y < 20.0 && enemy_cooldown.timer.just_finished() {
-                health.0 -= damage.0 as i64;
+            if x < 20.0 && y < 20.0 {
+                enemy_cooldown.timer.reset();
+
+                let blocked = stats.0.armor.clamp(0.0, 1.0);
+                let damage = (damage.0 as f32 * (1.0 - blocked)).round() as i64;
+
+                let knockback = knockback.copied().unwrap_or_default();
+
+                match defense::defend(blocking, sprite.flip_x, player_x, enemy_x, damage, stamina.current) {
+                    Defense::Parried => {
+                        // Turn the attack back on the attacker
+                        hits.send(Hit {
+                            target: enemy_entity,
+                            source: player_global_transform.translation().truncate(),
+                            knockback: STAGGER,
+                        });
+
+                        continue;
+                    }
+                    Defense::Blocked { damage, stamina: cost } => {
+                        health.current -= damage;
+                        stamina.current -= cost;
+
+                        hits.send(Hit {
+                            target: player_entity,
+                            source: global_transform.translation().truncate(),
+                            knockback: knockback.scaled(0.5),
+                        });
+                    }
+                    Defense::Hit => {
+                        health.current -= damage;
+
+                        hits.send(Hit {
+                            target: player_entity,
+                            source: global_transform.translation().truncate(),
+                            knockback,
+                        });
+                    }
+                }
             }
         }
     }
 }
 
-<<<<<<< HEAD
-fn print_health(player: Query<&Health, With<Player>>) {
-    if let Ok(health) = player.get_single() {
-        println!("Health: {}", health.0);
-    }
-}
-
 fn check_health(mut player: Query<(&Health, &mut Animation), With<Player>>) {
     if let Ok((health, mut animation)) = player.get_single_mut() {
         if let Animation::Death(_) = *animation {
@@ -782,20 +1553,29 @@ fn check_health(mut player: Query<(&Health, &mut Animation), With<Player>>) {
             return;
         }
 
-        if health.0 < 0 {
+        if health.current < 0 {
             *animation = Animation::Death(0);
         }
     }
 }
 
-fn player_attack(mut player: Query<(&Transform, &mut PlayerAttack)>, mut enemy: Query<(&Transform, &mut EnemyHealth)>, time: Res<Time>)  {
-    for (transform, mut attack) in player.iter_mut() {
-        for (player_transform, mut health) in enemy.iter_mut() {
-            let damage = match *attack {
-                PlayerAttack::Attack => 30,
-                PlayerAttack::ChargedAttack => 80,
-                PlayerAttack::None => 0,
-            };
+fn player_attack(mut player: Query<(&Transform, &GlobalTransform, &mut PlayerAttack, &EquipmentStats, &Level, &Knockback)>, mut enemy: Query<(Entity, &Transform, &mut EnemyHealth, &EnemyState)>, time: Res<Time>, mut hits: EventWriter<Hit>)  {
+    for (transform, global_transform, mut attack, stats, level, knockback) in player.iter_mut() {
+        let bonus = stats.0.damage + level.bonus_damage();
+
+        let damage = match *attack {
+            PlayerAttack::Attack => 30 + bonus,
+            PlayerAttack::ChargedAttack => 80 + bonus,
+            PlayerAttack::None => continue,
+        };
+
+        let range = 30.0 + stats.0.range;
+
+        for (enemy_entity, player_transform, mut health, state) in enemy.iter_mut() {
+            // Corpses keep their health around but can't be hit anymore
+            if let EnemyState::Dying | EnemyState::Dead(_) = state {
+                continue;
+            }
 
             let enemy_x = transform.translation.x;
             let enemy_y = transform.translation.y;
@@ -809,27 +1589,98 @@ fn player_attack(mut player: Query<(&Transform, &mut PlayerAttack)>, mut enemy:
             let x = x.abs();
             let y = y.abs();
 
-            if x < 30.0 && y < 30.0 {
-                health.0 -= damage as i64;
-                println!("damage: {}", damage);
+            if x < range && y < range {
+                health.0 -= damage;
+                debug!(damage, "Player hit an enemy");
+
+                let knockback = match *attack {
+                    PlayerAttack::ChargedAttack => knockback.scaled(2.0),
+                    _ => *knockback,
+                };
+
+                hits.send(Hit {
+                    target: enemy_entity,
+                    source: global_transform.translation().truncate(),
+                    knockback,
+                });
+
                 *attack = PlayerAttack::None;
+                break;
             }
         }
     }
 }
 
-fn check_enemy_health(mut enemy: Query<(&EnemyHealth, &mut EnemyDamage, &mut TextureAtlasSprite)>) {
-    if let Ok((health, mut damage, mut sprite)) = enemy.get_single_mut() {
-        println!("enemy health: {}", health.0);
+fn check_enemy_health(
+    mut commands: Commands,
+    mut enemy: Query<(Entity, Ref<EnemyHealth>, &mut EnemyState, &mut TextureAtlasSprite, &DeathAnimation, &Transform, &Parent)>,
+    mut died: EventWriter<EnemyDied>,
+) {
+    for (entity, health, mut state, mut sprite, death_animation, transform, parent) in enemy.iter_mut() {
+        if let EnemyState::Dying | EnemyState::Dead(_) = *state {
+            continue;
+        }
+
+        if health.0 <= 0 {
+            *state = EnemyState::Dying;
 
-        if health.0 < 0 {
-            damage.0 = 0;
-            
-            sprite.index = 100;
+            sprite.index = death_animation.first;
+            sprite.color = Color::WHITE;
+
+            // Corpses don't collide or attack anymore
+            commands
+                .entity(entity)
+                .remove::<(Collider, RigidBody, EnemyAttackCooldown, AiState, NavAgent)>();
+
+            died.send(EnemyDied {
+                enemy: entity,
+                parent: parent.get(),
+                translation: transform.translation,
+            });
+        } else if health.is_changed() && !health.is_added() {
+            *state = EnemyState::Hurt(Timer::new(Duration::from_secs_f32(0.2), TimerMode::Once));
+
+            sprite.color = Color::RED;
+        }
+    }
+}
+
+fn animate_enemy_state(
+    mut commands: Commands,
+    mut enemy: Query<(Entity, &mut EnemyState, &mut TextureAtlasSprite, &mut AnimationTimer, &DeathAnimation)>,
+    time: Res<Time>,
+) {
+    for (entity, mut state, mut sprite, mut animation_timer, death_animation) in enemy.iter_mut() {
+        match *state {
+            EnemyState::Alive => {}
+            EnemyState::Hurt(ref mut timer) => {
+                timer.tick(time.delta());
+
+                if timer.finished() {
+                    *state = EnemyState::Alive;
+                    sprite.color = Color::WHITE;
+                }
+            }
+            EnemyState::Dying => {
+                animation_timer.timer.tick(time.delta());
+
+                if animation_timer.timer.just_finished() {
+                    sprite.index += 1;
+
+                    if sprite.index >= death_animation.first + death_animation.frames - 1 {
+                        *state = EnemyState::Dead(Timer::new(Duration::from_secs(3), TimerMode::Once));
+                    }
+                }
+            }
+            EnemyState::Dead(ref mut timer) => {
+                timer.tick(time.delta());
+
+                if timer.finished() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
         }
     }
 }
-=======
 
->>>>>>> a5ba3f1f514c3cf0089aebefca9758069534dd11
 
@@ -0,0 +1,448 @@
+use std::{cmp::Ordering, collections::BinaryHeap, collections::VecDeque};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{GameState, Player};
+
+/// How many cells up an enemy can jump
+const MAX_JUMP_HEIGHT: i32 = 3;
+/// How many cells sideways a jump can cover
+const MAX_JUMP_DISTANCE: i32 = 2;
+/// How far an enemy is willing to drop down
+const MAX_DROP_HEIGHT: i32 = 8;
+
+/// How an agent gets from one cell to the next
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Link {
+    Walk,
+    Jump,
+    Drop,
+}
+
+impl Link {
+    fn cost(self, from: GridCoords, to: GridCoords) -> u32 {
+        let dx = (to.x - from.x).unsigned_abs();
+        let dy = (to.y - from.y).unsigned_abs();
+
+        match self {
+            Link::Walk => 1,
+            Link::Jump => 2 + dx + dy * 2,
+            Link::Drop => 1 + dx + dy / 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NavStep {
+    pub cell: GridCoords,
+    /// How this cell is reached from the previous one
+    pub link: Link,
+}
+
+/// Walkable cells of a level and how they connect
+/// A cell is walkable when it's empty and has a wall right below it
+#[derive(Default)]
+pub struct NavGraph {
+    grid_size: i32,
+    links: HashMap<GridCoords, Vec<(GridCoords, Link)>>,
+}
+
+impl NavGraph {
+    pub fn build(walls: &HashSet<GridCoords>, width: i32, height: i32, grid_size: i32) -> Self {
+        let is_wall = |x: i32, y: i32| walls.contains(&GridCoords { x, y });
+        let in_bounds = |x: i32, y: i32| x >= 0 && x < width && y >= 0 && y < height;
+        let is_walkable = |x: i32, y: i32| in_bounds(x, y) && !is_wall(x, y) && is_wall(x, y - 1);
+
+        let mut links: HashMap<GridCoords, Vec<(GridCoords, Link)>> = HashMap::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                if !is_walkable(x, y) {
+                    continue;
+                }
+
+                let from = GridCoords { x, y };
+                let cell_links = links.entry(from).or_default();
+
+                for dx in [-1, 1] {
+                    // Walking along the same surface
+                    if is_walkable(x + dx, y) {
+                        cell_links.push((GridCoords { x: x + dx, y }, Link::Walk));
+                        continue;
+                    }
+
+                    // Walking off a ledge and falling to the first surface below
+                    if in_bounds(x + dx, y) && !is_wall(x + dx, y) {
+                        if let Some(landing) = (1..=MAX_DROP_HEIGHT)
+                            .map(|dy| y - dy)
+                            .take_while(|&landing| !is_wall(x + dx, landing))
+                            .find(|&landing| is_walkable(x + dx, landing))
+                        {
+                            cell_links.push((
+                                GridCoords {
+                                    x: x + dx,
+                                    y: landing,
+                                },
+                                Link::Drop,
+                            ));
+                        }
+                    }
+                }
+
+                // Jumping up onto surfaces that have enough headroom above the start
+                for dy in 1..=MAX_JUMP_HEIGHT {
+                    if is_wall(x, y + dy) {
+                        break;
+                    }
+
+                    for dx in -MAX_JUMP_DISTANCE..=MAX_JUMP_DISTANCE {
+                        if is_walkable(x + dx, y + dy) {
+                            cell_links.push((
+                                GridCoords {
+                                    x: x + dx,
+                                    y: y + dy,
+                                },
+                                Link::Jump,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            grid_size: grid_size.max(1),
+            links,
+        }
+    }
+
+    /// The cell a position relative to the level lies in
+    pub fn cell_at(&self, translation: Vec3) -> GridCoords {
+        GridCoords {
+            x: (translation.x / self.grid_size as f32).floor() as i32,
+            y: (translation.y / self.grid_size as f32).floor() as i32,
+        }
+    }
+
+    /// The closest walkable cell at or below the given one
+    pub fn nearest_walkable(&self, cell: GridCoords) -> Option<GridCoords> {
+        (0..=MAX_DROP_HEIGHT)
+            .map(|dy| GridCoords {
+                x: cell.x,
+                y: cell.y - dy,
+            })
+            .find(|cell| self.links.contains_key(cell))
+    }
+
+    /// A* from `start` to `goal`, both have to be walkable
+    /// The returned path doesn't include `start`
+    pub fn find_path(&self, start: GridCoords, goal: GridCoords) -> Option<Vec<NavStep>> {
+        #[derive(PartialEq, Eq)]
+        struct Open {
+            estimate: u32,
+            cell: GridCoords,
+        }
+
+        // BinaryHeap is a max-heap, flip the ordering so the cheapest estimate comes first
+        impl Ord for Open {
+            fn cmp(&self, other: &Self) -> Ordering {
+                other.estimate.cmp(&self.estimate)
+            }
+        }
+
+        impl PartialOrd for Open {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        let heuristic =
+            |cell: GridCoords| (goal.x - cell.x).unsigned_abs() + (goal.y - cell.y).unsigned_abs();
+
+        if !self.links.contains_key(&start) || !self.links.contains_key(&goal) {
+            return None;
+        }
+
+        let mut open = BinaryHeap::new();
+        let mut cost: HashMap<GridCoords, u32> = HashMap::new();
+        let mut came_from: HashMap<GridCoords, NavStep> = HashMap::new();
+
+        cost.insert(start, 0);
+        open.push(Open {
+            estimate: heuristic(start),
+            cell: start,
+        });
+
+        while let Some(Open { cell, .. }) = open.pop() {
+            if cell == goal {
+                let mut path = Vec::new();
+                let mut current = goal;
+
+                while current != start {
+                    let step = came_from[&current];
+                    path.push(NavStep {
+                        cell: current,
+                        link: step.link,
+                    });
+                    current = step.cell;
+                }
+
+                path.reverse();
+
+                return Some(path);
+            }
+
+            let cell_cost = cost[&cell];
+
+            for &(next, link) in self.links.get(&cell).into_iter().flatten() {
+                let next_cost = cell_cost + link.cost(cell, next);
+
+                if cost.get(&next).is_some_and(|&known| known <= next_cost) {
+                    continue;
+                }
+
+                cost.insert(next, next_cost);
+                // `cell` here is where we came from, not where we're going
+                came_from.insert(next, NavStep { cell, link });
+                open.push(Open {
+                    estimate: next_cost + heuristic(next),
+                    cell: next,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// Navigation graphs keyed by the level entity they were built for
+#[derive(Resource, Default)]
+pub struct NavGraphs(pub HashMap<Entity, NavGraph>);
+
+/// Lets an enemy follow paths towards the player
+#[derive(Component)]
+pub struct NavAgent {
+    path: VecDeque<NavStep>,
+    repath: Timer,
+    cell: Option<GridCoords>,
+}
+
+impl Default for NavAgent {
+    fn default() -> Self {
+        Self {
+            path: VecDeque::new(),
+            repath: Timer::from_seconds(0.5, TimerMode::Repeating),
+            cell: None,
+        }
+    }
+}
+
+impl NavAgent {
+    /// Horizontal direction towards the next waypoint and whether it has to be jumped to
+    pub fn next_move(&self) -> Option<(f32, bool)> {
+        let cell = self.cell?;
+        let step = self.path.front()?;
+
+        Some((
+            (step.cell.x - cell.x).signum() as f32,
+            step.link == Link::Jump,
+        ))
+    }
+}
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavGraphs>()
+            .add_systems(Update, forget_unloaded_levels)
+            .add_systems(Update, plan_paths.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// Drops the graphs of levels that were despawned, whether unloaded or respawned by a hot-reload
+fn forget_unloaded_levels(mut graphs: ResMut<NavGraphs>, mut levels: RemovedComponents<LevelIid>) {
+    for level in levels.read() {
+        graphs.0.remove(&level);
+    }
+}
+
+fn plan_paths(
+    mut agents: Query<(&mut NavAgent, &Transform, &Parent), Without<Player>>,
+    player: Query<(&Transform, &Parent), With<Player>>,
+    parents: Query<&Parent, (Without<NavAgent>, Without<Player>)>,
+    graphs: Res<NavGraphs>,
+    time: Res<Time>,
+) {
+    // Entities are children of a layer, which is a child of the level
+    let level_of = |parent: &Parent| parents.get(parent.get()).ok().map(|level| level.get());
+
+    let Ok((player_transform, player_parent)) = player.get_single() else {
+        return;
+    };
+
+    let player_level = level_of(player_parent);
+
+    for (mut agent, transform, parent) in agents.iter_mut() {
+        let level = level_of(parent);
+
+        let Some(graph) = level.and_then(|level| graphs.0.get(&level)) else {
+            agent.path.clear();
+            continue;
+        };
+
+        // The agent's center sits above the surface it's standing on
+        let cell = graph.cell_at(transform.translation);
+        let cell = graph.nearest_walkable(cell).unwrap_or(cell);
+        agent.cell = Some(cell);
+
+        // Drop waypoints the agent already made it to
+        while agent.path.front().is_some_and(|step| step.cell == cell) {
+            agent.path.pop_front();
+        }
+
+        agent.repath.tick(time.delta());
+
+        if !agent.repath.just_finished() {
+            continue;
+        }
+
+        // Only chase within the same level
+        if level != player_level {
+            agent.path.clear();
+            continue;
+        }
+
+        let goal = graph.nearest_walkable(graph.cell_at(player_transform.translation));
+
+        agent.path = goal
+            .and_then(|goal| graph.find_path(cell, goal))
+            .map(VecDeque::from)
+            .unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: i32 = 10;
+    const HEIGHT: i32 = 10;
+
+    /// Parses a grid drawn top row first, `#` is a wall
+    fn walls(rows: &[&str]) -> HashSet<GridCoords> {
+        let top = rows.len() as i32 - 1;
+
+        rows.iter()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c == '#')
+                    .map(move |(x, _)| GridCoords {
+                        x: x as i32,
+                        y: top - row as i32,
+                    })
+            })
+            .collect()
+    }
+
+    fn link(graph: &NavGraph, from: (i32, i32), to: (i32, i32)) -> Option<Link> {
+        let from = GridCoords::new(from.0, from.1);
+        let to = GridCoords::new(to.0, to.1);
+
+        graph.links[&from]
+            .iter()
+            .find(|(cell, _)| *cell == to)
+            .map(|(_, link)| *link)
+    }
+
+    /// A floor with a one cell step up on the right
+    fn step() -> NavGraph {
+        let walls = walls(&["....######", "##########"]);
+
+        NavGraph::build(&walls, WIDTH, HEIGHT, 8)
+    }
+
+    #[test]
+    fn walks_along_a_floor() {
+        let graph = step();
+
+        assert_eq!(link(&graph, (1, 1), (2, 1)), Some(Link::Walk));
+        assert_eq!(link(&graph, (2, 1), (1, 1)), Some(Link::Walk));
+    }
+
+    #[test]
+    fn jumps_up_a_step() {
+        let graph = step();
+
+        assert_eq!(link(&graph, (3, 1), (4, 2)), Some(Link::Jump));
+        assert_eq!(link(&graph, (3, 1), (5, 2)), Some(Link::Jump));
+    }
+
+    #[test]
+    fn drops_off_a_ledge() {
+        let graph = step();
+
+        assert_eq!(link(&graph, (4, 2), (3, 1)), Some(Link::Drop));
+    }
+
+    #[test]
+    fn walls_are_not_walkable() {
+        let graph = step();
+
+        assert!(!graph.links.contains_key(&GridCoords::new(4, 1)));
+        assert!(!graph.links.contains_key(&GridCoords::new(1, 2)));
+    }
+
+    #[test]
+    fn finds_a_path_up_the_step() {
+        let graph = step();
+
+        let path = graph
+            .find_path(GridCoords::new(0, 1), GridCoords::new(8, 2))
+            .unwrap();
+
+        assert_eq!(path.last().unwrap().cell, GridCoords::new(8, 2));
+        assert!(path.iter().any(|step| step.link == Link::Jump));
+        assert!(!path.iter().any(|step| step.cell == GridCoords::new(0, 1)));
+    }
+
+    #[test]
+    fn no_path_to_an_unreachable_ledge() {
+        // The ledge is too high to jump onto and too far out to drop from
+        let walls = walls(&[
+            "......####",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "####......",
+        ]);
+        let graph = NavGraph::build(&walls, WIDTH, HEIGHT, 8);
+
+        assert!(graph.links.contains_key(&GridCoords::new(7, 6)));
+        assert_eq!(
+            graph.find_path(GridCoords::new(1, 1), GridCoords::new(7, 6)),
+            None
+        );
+        assert_eq!(
+            graph.find_path(GridCoords::new(7, 6), GridCoords::new(1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn no_path_to_a_wall() {
+        let graph = step();
+
+        assert_eq!(
+            graph.find_path(GridCoords::new(0, 1), GridCoords::new(4, 1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use bevy::{prelude::*, transform::TransformSystem};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::MainCamera;
+
+/// Scroll factors for layers that don't have one set in LDtk, by layer identifier
+/// Like LDtk's parallax factor, 0 moves with the level and 1 stays fixed to the camera
+const PARALLAX_LAYERS: &[(&str, Vec2)] = &[("Back_ground", Vec2::new(0.6, 0.3))];
+
+/// Copies of a parallax layer on either side of it, enough to cover the view at our zoom
+const REPEATS: i32 = 3;
+
+/// A background layer that scrolls slower than the level it's in and repeats horizontally
+#[derive(Component)]
+pub struct Parallax {
+    factor: Vec2,
+    /// Where LDtk put the layer, relative to its level
+    origin: Vec2,
+    size: Vec2,
+}
+
+/// One of the horizontal repeats of a parallax layer, `offset` layer widths away from it
+#[derive(Component)]
+struct ParallaxCopy {
+    layer: Entity,
+    offset: i32,
+}
+
+pub struct ParallaxPlugin;
+
+impl Plugin for ParallaxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, setup_parallax_layers).add_systems(
+            PostUpdate,
+            // After everything that moves the camera has had its say
+            scroll_parallax.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// The layer's scroll factor from LDtk, or from `PARALLAX_LAYERS` if LDtk doesn't give one
+fn parallax_factor(layer: &LayerMetadata, project: Option<&LdtkProject>) -> Option<Vec2> {
+    let from_ldtk = project
+        .and_then(|project| {
+            project
+                .json_data()
+                .defs
+                .layers
+                .iter()
+                .find(|definition| definition.uid == layer.layer_def_uid)
+        })
+        .map(|definition| Vec2::new(definition.parallax_factor_x, definition.parallax_factor_y))
+        .filter(|factor| *factor != Vec2::ZERO);
+
+    from_ldtk.or_else(|| {
+        PARALLAX_LAYERS
+            .iter()
+            .find(|(identifier, _)| *identifier == layer.identifier)
+            .map(|(_, factor)| *factor)
+    })
+}
+
+/// Marks parallax layers as they spawn and fills in their repeats with copies of their tiles
+fn setup_parallax_layers(
+    mut commands: Commands,
+    layers: Query<
+        (
+            Entity,
+            &LayerMetadata,
+            &Transform,
+            &Parent,
+            &TileStorage,
+            &TilemapSize,
+            &TilemapGridSize,
+            &TilemapTileSize,
+            &TilemapSpacing,
+            &TilemapTexture,
+            &TilemapType,
+        ),
+        Added<LayerMetadata>,
+    >,
+    tiles: Query<(
+        &TilePos,
+        &TileTextureIndex,
+        &TileFlip,
+        &TileColor,
+        &TileVisible,
+    )>,
+    levels: Query<&Parent, With<LevelIid>>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    for (
+        entity,
+        layer,
+        transform,
+        parent,
+        storage,
+        size,
+        grid_size,
+        tile_size,
+        spacing,
+        texture,
+        map_type,
+    ) in layers.iter()
+    {
+        // The layer's level is in a world, which knows which project it came from
+        let project = levels
+            .get(parent.get())
+            .ok()
+            .and_then(|world| ldtk_projects.get(world.get()).ok())
+            .and_then(|handle| ldtk_project_assets.get(handle));
+
+        let Some(factor) = parallax_factor(layer, project) else {
+            continue;
+        };
+
+        let width = (layer.c_wid * layer.grid_size) as f32;
+        let height = (layer.c_hei * layer.grid_size) as f32;
+
+        commands.entity(entity).insert(Parallax {
+            factor,
+            origin: transform.translation.truncate(),
+            size: Vec2::new(width, height),
+        });
+
+        for offset in (-REPEATS..=REPEATS).filter(|offset| *offset != 0) {
+            let copy = commands.spawn_empty().id();
+            let mut copy_storage = TileStorage::empty(*size);
+
+            for tile in storage.iter().flatten() {
+                let Ok((position, texture_index, flip, color, visible)) = tiles.get(*tile) else {
+                    continue;
+                };
+
+                let copy_tile = commands
+                    .spawn(TileBundle {
+                        position: *position,
+                        texture_index: *texture_index,
+                        tilemap_id: TilemapId(copy),
+                        visible: *visible,
+                        flip: *flip,
+                        color: *color,
+                        ..Default::default()
+                    })
+                    .id();
+
+                copy_storage.set(position, copy_tile);
+                commands.entity(copy).add_child(copy_tile);
+            }
+
+            commands
+                .entity(copy)
+                .insert((
+                    ParallaxCopy {
+                        layer: entity,
+                        offset,
+                    },
+                    TilemapBundle {
+                        grid_size: *grid_size,
+                        map_type: *map_type,
+                        size: *size,
+                        spacing: *spacing,
+                        storage: copy_storage,
+                        texture: texture.clone(),
+                        tile_size: *tile_size,
+                        transform: *transform,
+                        ..Default::default()
+                    },
+                ))
+                // Copies go away with the level like the layer itself
+                .set_parent(parent.get());
+        }
+    }
+}
+
+/// Offsets parallax layers from their level by how far the camera is from the level's center
+/// Uses wherever the camera ended up this frame
+fn scroll_parallax(
+    mut layers: Query<(&Parallax, &Parent, &mut Transform), Without<ParallaxCopy>>,
+    mut copies: Query<(&ParallaxCopy, &mut Transform), Without<Parallax>>,
+    levels: Query<&GlobalTransform>,
+    camera: Query<&Transform, (With<MainCamera>, Without<Parallax>, Without<ParallaxCopy>)>,
+) {
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+
+    for (parallax, parent, mut transform) in layers.iter_mut() {
+        let Ok(level) = levels.get(parent.get()) else {
+            continue;
+        };
+
+        // Levels are never rotated or scaled, so this is the camera relative to the level
+        let camera_in_level = camera.translation.truncate() - level.translation().truncate();
+        let from_center = camera_in_level - (parallax.origin + parallax.size / 2.0);
+
+        let mut position = parallax.origin + from_center * parallax.factor;
+
+        // Jump whole layer widths so the repeats always surround the camera
+        let behind = camera_in_level.x - (position.x + parallax.size.x / 2.0);
+        position.x += (behind / parallax.size.x).round() * parallax.size.x;
+
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+
+    for (copy, mut transform) in copies.iter_mut() {
+        let Ok((parallax, _, layer_transform)) = layers.get(copy.layer) else {
+            continue;
+        };
+
+        transform.translation =
+            layer_transform.translation + Vec3::X * parallax.size.x * copy.offset as f32;
+    }
+}
@@ -0,0 +1,384 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{combat::Hit, Debufs, DebufsEnum, EnemyDied, GameState};
+
+/// Particles are drawn above the level's layers
+const PARTICLE_Z: f32 = 10.0;
+
+/// How each particle of an effect looks and moves
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleSettings {
+    /// Seconds before a particle disappears
+    pub lifetime: f32,
+    pub velocity: Vec2,
+    /// Random extra velocity, up to this much either way on each axis
+    pub spread: Vec2,
+    /// Downwards acceleration, negative floats upwards
+    pub gravity: f32,
+    pub size: f32,
+    /// Color faded towards `end_color` over the particle's life
+    pub start_color: Color,
+    pub end_color: Color,
+}
+
+impl ParticleSettings {
+    pub const POISON: Self = Self {
+        lifetime: 1.2,
+        velocity: Vec2::new(0.0, 4.0),
+        spread: Vec2::new(3.0, 2.0),
+        gravity: 0.0,
+        size: 1.5,
+        start_color: Color::rgba(0.4, 0.8, 0.2, 0.6),
+        end_color: Color::rgba(0.2, 0.5, 0.1, 0.0),
+    };
+
+    pub const FIRE: Self = Self {
+        lifetime: 0.8,
+        velocity: Vec2::new(0.0, 20.0),
+        spread: Vec2::new(3.0, 6.0),
+        gravity: -10.0,
+        size: 1.5,
+        start_color: Color::rgba(1.0, 0.8, 0.2, 0.9),
+        end_color: Color::rgba(0.8, 0.1, 0.0, 0.0),
+    };
+
+    const DUST: Self = Self {
+        lifetime: 0.4,
+        velocity: Vec2::new(-15.0, 6.0),
+        spread: Vec2::new(10.0, 4.0),
+        gravity: 20.0,
+        size: 1.5,
+        start_color: Color::rgba(0.8, 0.75, 0.65, 0.8),
+        end_color: Color::rgba(0.8, 0.75, 0.65, 0.0),
+    };
+
+    const SPARKS: Self = Self {
+        lifetime: 0.25,
+        velocity: Vec2::new(50.0, 20.0),
+        spread: Vec2::new(30.0, 40.0),
+        gravity: 200.0,
+        size: 1.0,
+        start_color: Color::rgb(1.0, 1.0, 0.7),
+        end_color: Color::rgba(1.0, 0.5, 0.1, 0.0),
+    };
+
+    const BLOOD: Self = Self {
+        lifetime: 0.6,
+        velocity: Vec2::new(0.0, 30.0),
+        spread: Vec2::new(35.0, 25.0),
+        gravity: 300.0,
+        size: 1.5,
+        start_color: Color::rgb(0.7, 0.0, 0.05),
+        end_color: Color::rgba(0.4, 0.0, 0.0, 0.0),
+    };
+
+    pub fn debuff(debuf: DebufsEnum) -> Option<Self> {
+        match debuf {
+            DebufsEnum::Poison => Some(Self::POISON),
+            DebufsEnum::Fire => Some(Self::FIRE),
+            DebufsEnum::None => None,
+        }
+    }
+}
+
+/// Keeps spawning particles around its entity while enabled
+#[derive(Component, Clone)]
+pub struct Emitter {
+    pub settings: ParticleSettings,
+    /// Particles per second
+    pub rate: f32,
+    /// Half extents of the area particles start in
+    pub area: Vec2,
+    pub enabled: bool,
+    // Particles owed from previous frames
+    pending: f32,
+}
+
+impl Emitter {
+    pub fn new(settings: ParticleSettings, rate: f32, area: Vec2) -> Self {
+        Self {
+            settings,
+            rate,
+            area,
+            enabled: true,
+            pending: 0.0,
+        }
+    }
+}
+
+/// One-off effects
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Effect {
+    /// Kicked up when dashing or landing
+    Dust,
+    /// Something got hit
+    Sparks,
+    /// An enemy died
+    Blood,
+}
+
+impl Effect {
+    fn settings(self) -> ParticleSettings {
+        match self {
+            Effect::Dust => ParticleSettings::DUST,
+            Effect::Sparks => ParticleSettings::SPARKS,
+            Effect::Blood => ParticleSettings::BLOOD,
+        }
+    }
+
+    fn count(self) -> u32 {
+        match self {
+            Effect::Dust => 8,
+            Effect::Sparks => 10,
+            Effect::Blood => 16,
+        }
+    }
+}
+
+/// Plays `effect` once at `position`
+#[derive(Event)]
+pub struct Burst {
+    pub position: Vec2,
+    pub effect: Effect,
+    /// Which way the effect sprays, 1.0 as set up, -1.0 mirrored and 0.0 either way at random
+    pub direction: f32,
+}
+
+#[derive(Component)]
+struct Particle {
+    velocity: Vec2,
+    gravity: f32,
+    life: Timer,
+    start_color: Color,
+    end_color: Color,
+}
+
+/// The emitter showing one of its parent's debuffs
+#[derive(Component)]
+struct DebufEmitter(DebufsEnum);
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Burst>().add_systems(
+            Update,
+            (
+                bursts_from_combat,
+                play_bursts,
+                show_debuffs,
+                run_emitters,
+                update_particles,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+fn spawn_particle(
+    commands: &mut Commands,
+    rng: &mut impl Rng,
+    settings: &ParticleSettings,
+    position: Vec2,
+    direction: f32,
+) {
+    let direction = if direction == 0.0 {
+        if rng.gen_bool(0.5) {
+            1.0
+        } else {
+            -1.0
+        }
+    } else {
+        direction.signum()
+    };
+
+    let spread = Vec2::new(
+        rng.gen_range(-1.0..=1.0) * settings.spread.x,
+        rng.gen_range(-1.0..=1.0) * settings.spread.y,
+    );
+
+    commands.spawn((
+        Particle {
+            velocity: Vec2::new(settings.velocity.x * direction, settings.velocity.y) + spread,
+            gravity: settings.gravity,
+            life: Timer::from_seconds(settings.lifetime, TimerMode::Once),
+            start_color: settings.start_color,
+            end_color: settings.end_color,
+        },
+        SpriteBundle {
+            sprite: Sprite {
+                color: settings.start_color,
+                custom_size: Some(Vec2::splat(settings.size)),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(position.extend(PARTICLE_Z)),
+            ..Default::default()
+        },
+    ));
+}
+
+/// Sparks fly off whatever got hit, away from the attacker, and enemies bleed out when they die
+fn bursts_from_combat(
+    mut hits: EventReader<Hit>,
+    mut died: EventReader<EnemyDied>,
+    mut bursts: EventWriter<Burst>,
+    targets: Query<&GlobalTransform>,
+) {
+    for hit in hits.read() {
+        let Ok(transform) = targets.get(hit.target) else {
+            continue;
+        };
+
+        let position = transform.translation().truncate();
+
+        bursts.send(Burst {
+            // On the side the hit came from
+            position: position + (hit.source - position).clamp_length_max(4.0),
+            effect: Effect::Sparks,
+            direction: (position.x - hit.source.x).signum(),
+        });
+    }
+
+    for event in died.read() {
+        let Ok(layer) = targets.get(event.parent) else {
+            continue;
+        };
+
+        bursts.send(Burst {
+            position: layer.transform_point(event.translation).truncate(),
+            effect: Effect::Blood,
+            direction: 0.0,
+        });
+    }
+}
+
+fn play_bursts(mut commands: Commands, mut bursts: EventReader<Burst>) {
+    let mut rng = rand::thread_rng();
+
+    for burst in bursts.read() {
+        let settings = burst.effect.settings();
+
+        for _ in 0..burst.effect.count() {
+            spawn_particle(
+                &mut commands,
+                &mut rng,
+                &settings,
+                burst.position,
+                burst.direction,
+            );
+        }
+    }
+}
+
+/// Keeps an emitter on debuffed entities for each debuff they have
+fn show_debuffs(
+    mut commands: Commands,
+    debuffed: Query<(Entity, &Debufs, Option<&Children>), Changed<Debufs>>,
+    emitters: Query<&DebufEmitter>,
+) {
+    for (entity, debufs, children) in debuffed.iter() {
+        let mut shown = Vec::new();
+
+        for child in children.into_iter().flatten() {
+            let Ok(emitter) = emitters.get(*child) else {
+                continue;
+            };
+
+            if debufs.debufs.contains(&emitter.0) {
+                shown.push(emitter.0);
+            } else {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+
+        for debuf in &debufs.debufs {
+            let Some(settings) = ParticleSettings::debuff(*debuf) else {
+                continue;
+            };
+
+            if shown.contains(debuf) {
+                continue;
+            }
+
+            shown.push(*debuf);
+
+            let emitter = commands
+                .spawn((
+                    DebufEmitter(*debuf),
+                    Emitter::new(settings, 12.0, Vec2::new(4.0, 6.0)),
+                    SpatialBundle::default(),
+                ))
+                .id();
+
+            commands.entity(entity).add_child(emitter);
+        }
+    }
+}
+
+fn run_emitters(
+    mut commands: Commands,
+    mut emitters: Query<(&mut Emitter, &GlobalTransform)>,
+    time: Res<Time>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (mut emitter, transform) in emitters.iter_mut() {
+        if !emitter.enabled {
+            emitter.pending = 0.0;
+            continue;
+        }
+
+        emitter.pending += emitter.rate * time.delta_seconds();
+
+        let center = transform.translation().truncate();
+
+        while emitter.pending >= 1.0 {
+            emitter.pending -= 1.0;
+
+            let offset = Vec2::new(
+                rng.gen_range(-1.0..=1.0) * emitter.area.x,
+                rng.gen_range(-1.0..=1.0) * emitter.area.y,
+            );
+
+            spawn_particle(
+                &mut commands,
+                &mut rng,
+                &emitter.settings,
+                center + offset,
+                0.0,
+            );
+        }
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+
+    for (entity, mut particle, mut transform, mut sprite) in particles.iter_mut() {
+        particle.life.tick(time.delta());
+
+        if particle.life.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        particle.velocity.y -= particle.gravity * delta;
+        transform.translation += (particle.velocity * delta).extend(0.0);
+
+        let t = particle.life.percent();
+        let (start, end) = (particle.start_color.as_rgba(), particle.end_color.as_rgba());
+
+        sprite.color = Color::rgba(
+            start.r() + (end.r() - start.r()) * t,
+            start.g() + (end.g() - start.g()) * t,
+            start.b() + (end.b() - start.b()) * t,
+            start.a() + (end.a() - start.a()) * t,
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::{collision::Layer, EnemyState, GameState, Player};
+
+/// How long an enemy keeps hunting after losing sight of the player
+const ALERT_MEMORY: f32 = 4.0;
+/// How long an enemy keeps looking around after hearing something
+const SUSPICION_MEMORY: f32 = 3.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug, Deserialize)]
+pub enum AlertLevel {
+    #[default]
+    Unaware,
+    /// Heard something and is going to take a look
+    Suspicious,
+    /// Saw the player
+    Alerted,
+}
+
+/// How well an enemy notices the player
+#[derive(Component, Clone)]
+pub struct Perception {
+    pub vision_range: f32,
+    /// Half the width of the vision cone, in degrees
+    pub vision_angle: f32,
+    pub hearing_radius: f32,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            vision_range: 80.0,
+            vision_angle: 50.0,
+            hearing_radius: 60.0,
+        }
+    }
+}
+
+/// What an enemy currently knows about the player
+#[derive(Component)]
+pub struct Awareness {
+    pub level: AlertLevel,
+    /// Where the player was last seen or heard, in world space
+    pub last_known: Option<Vec2>,
+    facing: f32,
+    forget: Timer,
+}
+
+impl Default for Awareness {
+    fn default() -> Self {
+        Self {
+            level: AlertLevel::Unaware,
+            last_known: None,
+            facing: 1.0,
+            forget: Timer::from_seconds(SUSPICION_MEMORY, TimerMode::Once),
+        }
+    }
+}
+
+impl Awareness {
+    fn raise(&mut self, level: AlertLevel, position: Vec2) {
+        let memory = match level {
+            AlertLevel::Alerted => ALERT_MEMORY,
+            _ => SUSPICION_MEMORY,
+        };
+
+        self.level = self.level.max(level);
+        self.last_known = Some(position);
+        self.forget = Timer::from_seconds(memory, TimerMode::Once);
+    }
+}
+
+/// Something loud the player did
+#[derive(Event)]
+pub struct Noise {
+    pub position: Vec2,
+    /// Scales how far away enemies can hear it
+    pub loudness: f32,
+}
+
+/// The "?" or "!" shown above an enemy
+#[derive(Component)]
+struct AlertIndicator;
+
+pub struct PerceptionPlugin;
+
+impl Plugin for PerceptionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Noise>().add_systems(
+            Update,
+            (perceive, spawn_alert_indicators, update_alert_indicators)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+fn perceive(
+    mut enemies: Query<
+        (
+            Entity,
+            &Perception,
+            &mut Awareness,
+            &EnemyState,
+            &GlobalTransform,
+            &Velocity,
+        ),
+        Without<Player>,
+    >,
+    player: Query<(Entity, &GlobalTransform), With<Player>>,
+    mut noises: EventReader<Noise>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let noises: Vec<&Noise> = noises.read().collect();
+    let player = player.get_single().ok();
+
+    for (entity, perception, mut awareness, state, global_transform, velocity) in enemies.iter_mut()
+    {
+        if let EnemyState::Dying | EnemyState::Dead(_) = state {
+            continue;
+        }
+
+        // Enemies look where they last walked
+        if velocity.linvel.x.abs() > 1.0 {
+            awareness.facing = velocity.linvel.x.signum();
+        }
+
+        let origin = global_transform.translation().truncate();
+
+        let seen = player.and_then(|(player_entity, player_transform)| {
+            let player_position = player_transform.translation().truncate();
+            let to_player = player_position - origin;
+            let distance = to_player.length();
+
+            if distance > perception.vision_range {
+                return None;
+            }
+
+            let forward = Vec2::new(awareness.facing, 0.0);
+
+            if distance > 0.0
+                && forward.angle_between(to_player).abs().to_degrees() > perception.vision_angle
+            {
+                return None;
+            }
+
+            let filter = QueryFilter::default()
+                .groups(CollisionGroups::new(Group::ALL, Layer::World.group()))
+                .exclude_collider(entity)
+                .exclude_collider(player_entity)
+                .exclude_sensors();
+
+            let blocked = rapier_context
+                .cast_ray(
+                    origin,
+                    to_player.normalize_or_zero(),
+                    distance,
+                    true,
+                    filter,
+                )
+                .is_some();
+
+            (!blocked).then_some(player_position)
+        });
+
+        let heard = noises
+            .iter()
+            .find(|noise| {
+                noise.position.distance(origin) <= perception.hearing_radius * noise.loudness
+            })
+            .map(|noise| noise.position);
+
+        if let Some(position) = seen {
+            awareness.raise(AlertLevel::Alerted, position);
+        } else if let Some(position) = heard {
+            awareness.raise(AlertLevel::Suspicious, position);
+        } else if awareness.level != AlertLevel::Unaware {
+            awareness.forget.tick(time.delta());
+
+            if awareness.forget.just_finished() {
+                match awareness.level {
+                    AlertLevel::Alerted => {
+                        awareness.level = AlertLevel::Suspicious;
+                        awareness.forget = Timer::from_seconds(SUSPICION_MEMORY, TimerMode::Once);
+                    }
+                    _ => {
+                        awareness.level = AlertLevel::Unaware;
+                        awareness.last_known = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn spawn_alert_indicators(mut commands: Commands, enemies: Query<Entity, Added<Awareness>>) {
+    for entity in enemies.iter() {
+        commands.entity(entity).with_children(|enemy| {
+            enemy.spawn((
+                AlertIndicator,
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 48.0,
+                            ..Default::default()
+                        },
+                    ),
+                    // The camera is zoomed way in, shrink the text back down
+                    transform: Transform::from_xyz(0.0, 24.0, 10.0).with_scale(Vec3::splat(0.2)),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                },
+            ));
+        });
+    }
+}
+
+fn update_alert_indicators(
+    enemies: Query<(&Awareness, &EnemyState, &Children)>,
+    mut indicators: Query<(&mut Text, &mut Visibility), With<AlertIndicator>>,
+) {
+    for (awareness, state, children) in enemies.iter() {
+        for child in children.iter() {
+            let Ok((mut text, mut visibility)) = indicators.get_mut(*child) else {
+                continue;
+            };
+
+            let (symbol, color) = match (awareness.level, state) {
+                (_, EnemyState::Dying | EnemyState::Dead(_)) | (AlertLevel::Unaware, _) => {
+                    *visibility = Visibility::Hidden;
+                    continue;
+                }
+                (AlertLevel::Suspicious, _) => ("?", Color::YELLOW),
+                (AlertLevel::Alerted, _) => ("!", Color::RED),
+            };
+
+            *visibility = Visibility::Inherited;
+            text.sections[0].value = symbol.to_string();
+            text.sections[0].style.color = color;
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    collision::{entity_collider, Layer},
+    inventory::Inventory,
+    Debufs, DebufsEnum, GameState, Health, Player, Stamina,
+};
+
+/// Something the player can pick up off the ground
+#[derive(Component, Clone, Debug)]
+pub enum Pickup {
+    Health(i64),
+    Stamina(i64),
+    Cure(DebufsEnum),
+    Key(String),
+    /// Goes into the inventory, by item id
+    Item(String),
+}
+
+impl Default for Pickup {
+    fn default() -> Self {
+        Self::Health(25)
+    }
+}
+
+impl Pickup {
+    /// Placeholder look until pickups get proper sprites
+    fn color(&self) -> Color {
+        match self {
+            Pickup::Health(_) => Color::CRIMSON,
+            Pickup::Stamina(_) => Color::LIME_GREEN,
+            Pickup::Cure(_) => Color::TEAL,
+            Pickup::Key(_) => Color::GOLD,
+            Pickup::Item(_) => Color::SILVER,
+        }
+    }
+
+    /// Builds the pickup from an LDtk entity, reading the optional `amount`, `key` and `item` fields
+    fn from_entity_instance(entity_instance: &EntityInstance) -> Self {
+        let amount = |default: i64| {
+            entity_instance
+                .get_int_field("amount")
+                .map(|amount| *amount as i64)
+                .unwrap_or(default)
+        };
+
+        match entity_instance.identifier.as_str() {
+            "StaminaOrb" => Pickup::Stamina(amount(30)),
+            "Antidote" => Pickup::Cure(DebufsEnum::Poison),
+            "FireSalve" => Pickup::Cure(DebufsEnum::Fire),
+            "Key" => Pickup::Key(
+                entity_instance
+                    .get_string_field("key")
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            "Item" => Pickup::Item(
+                entity_instance
+                    .get_string_field("item")
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            _ => Pickup::Health(amount(25)),
+        }
+    }
+}
+
+/// Keys the player is carrying, by name
+#[derive(Default, Component)]
+pub struct Keys(pub HashSet<String>);
+
+#[derive(Bundle)]
+pub struct PickupBundle {
+    pickup: Pickup,
+    sprite_bundle: SpriteBundle,
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for PickupBundle {
+    fn default() -> Self {
+        Self {
+            pickup: Pickup::default(),
+            sprite_bundle: SpriteBundle::default(),
+            collider: Collider::ball(3.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+impl PickupBundle {
+    pub fn new(pickup: Pickup, translation: Vec3) -> Self {
+        Self {
+            sprite_bundle: SpriteBundle {
+                sprite: Sprite {
+                    color: pickup.color(),
+                    custom_size: Some(Vec2::splat(6.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(translation),
+                ..Default::default()
+            },
+            pickup,
+            ..Default::default()
+        }
+    }
+}
+
+/// Pickups placed by hand in LDtk
+#[derive(Bundle, LdtkEntity)]
+struct LdtkPickupBundle {
+    #[with(Pickup::from_entity_instance)]
+    pickup: Pickup,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for LdtkPickupBundle {
+    fn default() -> Self {
+        Self {
+            pickup: Pickup::default(),
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::ball(3.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+/// A door that stays shut until the player touches it while holding the matching key
+#[derive(Default, Component)]
+struct LockedDoor {
+    key: String,
+}
+
+impl LockedDoor {
+    fn from_entity_instance(entity_instance: &EntityInstance) -> Self {
+        Self {
+            key: entity_instance
+                .get_string_field("key")
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Bundle, LdtkEntity)]
+struct LockedDoorBundle {
+    #[with(LockedDoor::from_entity_instance)]
+    door: LockedDoor,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(entity_collider)]
+    collider: Collider,
+    rigid_body: RigidBody,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
+}
+
+impl Default for LockedDoorBundle {
+    fn default() -> Self {
+        Self {
+            door: LockedDoor::default(),
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::cuboid(4.0, 8.0),
+            rigid_body: RigidBody::Fixed,
+            collision_group: Layer::World.collision_groups(),
+            solver_group: Layer::World.solver_groups(),
+        }
+    }
+}
+
+pub struct PickupsPlugin;
+
+impl Plugin for PickupsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (collect_pickups, open_locked_doors).run_if(in_state(GameState::Playing)),
+        )
+        .register_ldtk_entity::<LdtkPickupBundle>("HealthPotion")
+        .register_ldtk_entity::<LdtkPickupBundle>("StaminaOrb")
+        .register_ldtk_entity::<LdtkPickupBundle>("Antidote")
+        .register_ldtk_entity::<LdtkPickupBundle>("FireSalve")
+        .register_ldtk_entity::<LdtkPickupBundle>("Key")
+        .register_ldtk_entity::<LdtkPickupBundle>("Item")
+        .register_ldtk_entity::<LockedDoorBundle>("LockedDoor");
+    }
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &mut Health,
+            &mut Stamina,
+            &mut Debufs,
+            &mut Keys,
+            &mut Inventory,
+        ),
+        With<Player>,
+    >,
+    pickups: Query<&Pickup>,
+    rapier_context: Res<RapierContext>,
+) {
+    let Ok((player_entity, mut health, mut stamina, mut debufs, mut keys, mut inventory)) =
+        player.get_single_mut()
+    else {
+        return;
+    };
+
+    for (a, b, intersecting) in rapier_context.intersection_pairs_with(player_entity) {
+        if !intersecting {
+            continue;
+        }
+
+        let other = if a == player_entity { b } else { a };
+
+        let Ok(pickup) = pickups.get(other) else {
+            continue;
+        };
+
+        match pickup {
+            Pickup::Health(amount) => health.heal(*amount),
+            Pickup::Stamina(amount) => stamina.recover(*amount),
+            Pickup::Cure(cured) => debufs.debufs.retain(|debuf| debuf != cured),
+            Pickup::Key(key) => {
+                keys.0.insert(key.clone());
+            }
+            Pickup::Item(item) => inventory.items.push(item.clone()),
+        }
+
+        commands.entity(other).despawn_recursive();
+    }
+}
+
+fn open_locked_doors(
+    mut commands: Commands,
+    mut player: Query<(Entity, &mut Keys), With<Player>>,
+    doors: Query<(Entity, &LockedDoor)>,
+    rapier_context: Res<RapierContext>,
+) {
+    let Ok((player_entity, mut keys)) = player.get_single_mut() else {
+        return;
+    };
+
+    for (door_entity, door) in doors.iter() {
+        let touching = rapier_context
+            .contact_pair(player_entity, door_entity)
+            .is_some_and(|contact| contact.has_any_active_contacts());
+
+        if touching && keys.0.remove(&door.key) {
+            commands.entity(door_entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    collision::{entity_collider, Layer},
+    GameState, Player,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+enum PlatformMode {
+    /// Goes back and forth along its path forever
+    #[default]
+    Loop,
+    /// Rides to the other end of its path every time the player steps on
+    Triggered,
+}
+
+/// A platform following the `path` points set in LDtk at `speed` pixels per second
+#[derive(Component, Default)]
+pub struct MovingPlatform {
+    mode: PlatformMode,
+    speed: f32,
+    // LDtk cells, turned into waypoints once we know the grid size of the platform's layer
+    start_cell: IVec2,
+    path_cells: Vec<IVec2>,
+    waypoints: Vec<Vec2>,
+    target: usize,
+    forward: bool,
+    moving: bool,
+    /// Whether the player stood on it last frame
+    ridden: bool,
+}
+
+impl MovingPlatform {
+    const DEFAULT_SPEED: f32 = 30.0;
+
+    fn from_entity_instance(entity_instance: &EntityInstance, mode: PlatformMode) -> Self {
+        Self {
+            mode,
+            speed: entity_instance
+                .get_float_field("speed")
+                .copied()
+                .unwrap_or(Self::DEFAULT_SPEED),
+            start_cell: entity_instance.grid,
+            path_cells: entity_instance
+                .get_points_field("path")
+                .map(|points| points.iter().copied().collect())
+                .unwrap_or_default(),
+            waypoints: Vec::new(),
+            target: 1,
+            forward: true,
+            moving: mode == PlatformMode::Loop,
+            ridden: false,
+        }
+    }
+}
+
+fn moving_platform(entity_instance: &EntityInstance) -> MovingPlatform {
+    MovingPlatform::from_entity_instance(entity_instance, PlatformMode::Loop)
+}
+
+fn elevator(entity_instance: &EntityInstance) -> MovingPlatform {
+    MovingPlatform::from_entity_instance(entity_instance, PlatformMode::Triggered)
+}
+
+/// Platforms only move the player through `ride_platforms`, not by dragging them along
+const PLATFORM_FRICTION: Friction = Friction {
+    coefficient: 0.0,
+    combine_rule: CoefficientCombineRule::Min,
+};
+
+#[derive(Bundle, LdtkEntity)]
+struct MovingPlatformBundle {
+    #[with(moving_platform)]
+    platform: MovingPlatform,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(entity_collider)]
+    collider: Collider,
+    rigid_body: RigidBody,
+    velocity: Velocity,
+    friction: Friction,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
+}
+
+impl Default for MovingPlatformBundle {
+    fn default() -> Self {
+        Self {
+            platform: MovingPlatform::default(),
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::cuboid(16.0, 4.0),
+            rigid_body: RigidBody::KinematicVelocityBased,
+            velocity: Velocity::zero(),
+            friction: PLATFORM_FRICTION,
+            collision_group: Layer::World.collision_groups(),
+            solver_group: Layer::World.solver_groups(),
+        }
+    }
+}
+
+#[derive(Bundle, LdtkEntity)]
+struct ElevatorBundle {
+    #[with(elevator)]
+    platform: MovingPlatform,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(entity_collider)]
+    collider: Collider,
+    rigid_body: RigidBody,
+    velocity: Velocity,
+    friction: Friction,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
+}
+
+impl Default for ElevatorBundle {
+    fn default() -> Self {
+        Self {
+            platform: MovingPlatform::default(),
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::cuboid(16.0, 4.0),
+            rigid_body: RigidBody::KinematicVelocityBased,
+            velocity: Velocity::zero(),
+            friction: PLATFORM_FRICTION,
+            collision_group: Layer::World.collision_groups(),
+            solver_group: Layer::World.solver_groups(),
+        }
+    }
+}
+
+#[derive(Default)]
+enum Crumble {
+    #[default]
+    Solid,
+    /// Stood on, about to give way
+    Cracking(Timer),
+    /// Dropping out of the level, comes back when the timer is done
+    Falling(Timer),
+}
+
+/// A platform that falls a moment after the player steps on it, then grows back
+#[derive(Component, Default)]
+pub struct CrumblingPlatform {
+    state: Crumble,
+    origin: Option<Vec3>,
+}
+
+impl CrumblingPlatform {
+    const CRACK_TIME: f32 = 0.5;
+    const RESPAWN_TIME: f32 = 3.0;
+}
+
+#[derive(Bundle, LdtkEntity)]
+struct CrumblingPlatformBundle {
+    platform: CrumblingPlatform,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(entity_collider)]
+    collider: Collider,
+    rigid_body: RigidBody,
+    velocity: Velocity,
+    locked_axes: LockedAxes,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
+}
+
+impl Default for CrumblingPlatformBundle {
+    fn default() -> Self {
+        Self {
+            platform: CrumblingPlatform::default(),
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::cuboid(8.0, 4.0),
+            rigid_body: RigidBody::Fixed,
+            velocity: Velocity::zero(),
+            locked_axes: LockedAxes::ROTATION_LOCKED,
+            collision_group: Layer::World.collision_groups(),
+            solver_group: Layer::World.solver_groups(),
+        }
+    }
+}
+
+pub struct PlatformsPlugin;
+
+impl Plugin for PlatformsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                build_platform_paths,
+                ride_platforms,
+                move_platforms,
+                crumble_platforms,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        )
+        .register_ldtk_entity::<MovingPlatformBundle>("MovingPlatform")
+        .register_ldtk_entity::<ElevatorBundle>("Elevator")
+        .register_ldtk_entity::<CrumblingPlatformBundle>("CrumblingPlatform");
+    }
+}
+
+/// Path points are LDtk cells, relative to the platform they're offsets in the layer's grid
+fn build_platform_paths(
+    mut platforms: Query<(&mut MovingPlatform, &Transform, &Parent), Added<MovingPlatform>>,
+    layers: Query<&LayerMetadata>,
+) {
+    for (mut platform, transform, parent) in platforms.iter_mut() {
+        let Ok(layer) = layers.get(parent.get()) else {
+            continue;
+        };
+
+        let grid_size = layer.grid_size as f32;
+        let start = transform.translation.truncate();
+        let start_cell = platform.start_cell;
+
+        let path: Vec<Vec2> = platform
+            .path_cells
+            .iter()
+            .map(|cell| {
+                // LDtk's y axis points down
+                start
+                    + Vec2::new(
+                        (cell.x - start_cell.x) as f32,
+                        (start_cell.y - cell.y) as f32,
+                    ) * grid_size
+            })
+            .collect();
+
+        platform.waypoints = std::iter::once(start).chain(path).collect();
+    }
+}
+
+/// Finds what the player is standing on, carries them along with moving platforms and sets
+/// off elevators and crumbling platforms
+fn ride_platforms(
+    mut player: Query<(Entity, &mut Transform, &GlobalTransform, &Collider), With<Player>>,
+    mut platforms: Query<(Entity, &mut MovingPlatform, &Velocity), Without<Player>>,
+    mut crumbling: Query<(Entity, &mut CrumblingPlatform, &Transform), Without<Player>>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let Ok((player_entity, mut player_transform, global_transform, collider)) =
+        player.get_single_mut()
+    else {
+        return;
+    };
+
+    let half_height = collider
+        .as_cuboid()
+        .map(|cuboid| cuboid.half_extents().y)
+        .unwrap_or(0.0);
+
+    let filter = QueryFilter::default()
+        .groups(CollisionGroups::new(
+            Layer::Player.group(),
+            Layer::World.group(),
+        ))
+        .exclude_collider(player_entity)
+        .exclude_sensors();
+
+    let ground = rapier_context
+        .cast_ray(
+            global_transform.translation().truncate(),
+            Vec2::NEG_Y,
+            half_height + 1.0,
+            true,
+            filter,
+        )
+        .map(|(entity, _)| entity);
+
+    for (entity, mut platform, velocity) in platforms.iter_mut() {
+        let ridden = ground == Some(entity);
+
+        if ridden {
+            // Rising platforms already push the player up through the contact, carrying them
+            // up as well would move them twice. Sinking ones would leave them floating.
+            let carry = Vec2::new(velocity.linvel.x, velocity.linvel.y.min(0.0));
+
+            player_transform.translation += (carry * time.delta_seconds()).extend(0.0);
+
+            // Elevators set off when stepped onto, not while stood on
+            if platform.mode == PlatformMode::Triggered && !platform.ridden && !platform.moving {
+                platform.moving = true;
+            }
+        }
+
+        platform.ridden = ridden;
+    }
+
+    for (entity, mut platform, transform) in crumbling.iter_mut() {
+        platform.origin.get_or_insert(transform.translation);
+
+        if ground == Some(entity) {
+            if let Crumble::Solid = platform.state {
+                platform.state = Crumble::Cracking(Timer::from_seconds(
+                    CrumblingPlatform::CRACK_TIME,
+                    TimerMode::Once,
+                ));
+            }
+        }
+    }
+}
+
+fn move_platforms(
+    mut platforms: Query<(&mut MovingPlatform, &Transform, &mut Velocity)>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+
+    for (mut platform, transform, mut velocity) in platforms.iter_mut() {
+        let last = platform.waypoints.len().saturating_sub(1);
+
+        if !platform.moving || last == 0 || delta == 0.0 {
+            velocity.linvel = Vec2::ZERO;
+            continue;
+        }
+
+        let to_target = platform.waypoints[platform.target] - transform.translation.truncate();
+
+        if to_target.length() <= platform.speed * delta {
+            let at_end = platform.target == 0 || platform.target == last;
+
+            // Turn around at either end of the path
+            if at_end {
+                platform.forward = platform.target == 0;
+            }
+
+            platform.target = if platform.forward {
+                platform.target + 1
+            } else {
+                platform.target - 1
+            };
+
+            if at_end && platform.mode == PlatformMode::Triggered {
+                platform.moving = false;
+            }
+        }
+
+        // Don't overshoot the waypoint
+        velocity.linvel =
+            to_target.normalize_or_zero() * platform.speed.min(to_target.length() / delta);
+    }
+}
+
+fn crumble_platforms(
+    mut commands: Commands,
+    mut platforms: Query<(
+        Entity,
+        &mut CrumblingPlatform,
+        &mut Transform,
+        &mut Velocity,
+    )>,
+    time: Res<Time>,
+) {
+    for (entity, mut platform, mut transform, mut velocity) in platforms.iter_mut() {
+        let origin = platform.origin;
+
+        match platform.state {
+            Crumble::Solid => {}
+            Crumble::Cracking(ref mut timer) => {
+                timer.tick(time.delta());
+
+                // Shake a little before giving way
+                if let Some(origin) = origin {
+                    let shake = (timer.elapsed_secs() * 60.0).sin() * 0.5;
+                    transform.translation.x = origin.x + shake;
+                }
+
+                if timer.finished() {
+                    // Falls through everything instead of landing somewhere as a new floor
+                    commands.entity(entity).insert((
+                        RigidBody::Dynamic,
+                        CollisionGroups::new(Layer::World.group(), Group::NONE),
+                    ));
+                    platform.state = Crumble::Falling(Timer::from_seconds(
+                        CrumblingPlatform::RESPAWN_TIME,
+                        TimerMode::Once,
+                    ));
+                }
+            }
+            Crumble::Falling(ref mut timer) => {
+                timer.tick(time.delta());
+
+                if timer.finished() {
+                    commands
+                        .entity(entity)
+                        .insert((RigidBody::Fixed, Layer::World.collision_groups()));
+
+                    if let Some(origin) = origin {
+                        transform.translation = origin;
+                    }
+
+                    *velocity = Velocity::zero();
+                    platform.state = Crumble::Solid;
+                }
+            }
+        }
+    }
+}
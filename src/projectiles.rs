@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    defense::{self, Blocking, Defense},
+    inventory::EquipmentStats,
+    Bullet, BulletBundle, BulletType, EnemyHealth, GameState, Health, Invulnerable, Player,
+    Stamina,
+};
+
+pub struct ProjectilesPlugin;
+
+impl Plugin for ProjectilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (expire_bullets, bullet_hits).run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+fn expire_bullets(
+    mut commands: Commands,
+    mut bullets: Query<(Entity, &mut Bullet)>,
+    time: Res<Time>,
+) {
+    for (entity, mut bullet) in bullets.iter_mut() {
+        bullet.lifetime.tick(time.delta());
+
+        if bullet.lifetime.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Collision groups already make sure bullets only touch what they can hit (or walls),
+/// so any intersection ends the bullet
+/// Enemy bullets parried by the player fly back as player bullets instead
+fn bullet_hits(
+    mut commands: Commands,
+    bullets: Query<(
+        Entity,
+        &Bullet,
+        &BulletType,
+        &Transform,
+        &GlobalTransform,
+        &Velocity,
+        Option<&Parent>,
+    )>,
+    mut player: Query<
+        (
+            &mut Health,
+            &mut Stamina,
+            &EquipmentStats,
+            &GlobalTransform,
+            &TextureAtlasSprite,
+            Option<&Blocking>,
+            Has<Invulnerable>,
+        ),
+        With<Player>,
+    >,
+    mut enemies: Query<&mut EnemyHealth>,
+    rapier_context: Res<RapierContext>,
+) {
+    for (bullet_entity, bullet, bullet_type, transform, global_transform, velocity, parent) in
+        bullets.iter()
+    {
+        for (a, b, intersecting) in rapier_context.intersection_pairs_with(bullet_entity) {
+            if !intersecting {
+                continue;
+            }
+
+            let other = if a == bullet_entity { b } else { a };
+
+            match bullet_type {
+                BulletType::Enemy => {
+                    if let Ok((
+                        mut health,
+                        mut stamina,
+                        stats,
+                        player_transform,
+                        sprite,
+                        blocking,
+                        invulnerable,
+                    )) = player.get_mut(other)
+                    {
+                        // Dashing through a bullet dodges it
+                        if invulnerable {
+                            continue;
+                        }
+
+                        let blocked = stats.0.armor.clamp(0.0, 1.0);
+                        let damage = (bullet.damage as f32 * (1.0 - blocked)).round() as i64;
+
+                        match defense::defend(
+                            blocking,
+                            sprite.flip_x,
+                            player_transform.translation().x,
+                            global_transform.translation().x,
+                            damage,
+                            stamina.current,
+                        ) {
+                            Defense::Parried => {
+                                let reflected = commands
+                                    .spawn(BulletBundle::new(
+                                        BulletType::Player,
+                                        bullet.damage,
+                                        transform.translation,
+                                        -velocity.linvel,
+                                    ))
+                                    .id();
+
+                                if let Some(parent) = parent {
+                                    commands.entity(parent.get()).add_child(reflected);
+                                }
+                            }
+                            Defense::Blocked {
+                                damage,
+                                stamina: cost,
+                            } => {
+                                health.current -= damage;
+                                stamina.current -= cost;
+                            }
+                            Defense::Hit => health.current -= damage,
+                        }
+                    }
+                }
+                BulletType::Player => {
+                    if let Ok(mut health) = enemies.get_mut(other) {
+                        health.0 -= bullet.damage;
+                    }
+                }
+            }
+
+            commands.entity(bullet_entity).despawn_recursive();
+            break;
+        }
+    }
+}
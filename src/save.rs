@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, fs};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    interactions::{LeverPulled, Signals},
+    leveling::LevelUp,
+    GameState, Level, Player,
+};
+
+const SAVE_PATH: &str = "save.ron";
+
+/// Everything that survives between play sessions
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct SaveData {
+    pub level: u8,
+    pub experience: u64,
+    /// Per LDtk level iid
+    pub levels: BTreeMap<String, LevelSave>,
+}
+
+/// What changed in one level
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LevelSave {
+    /// Lever states by LDtk entity iid
+    pub levers: BTreeMap<String, bool>,
+}
+
+impl SaveData {
+    fn read() -> Option<Self> {
+        let contents = fs::read_to_string(SAVE_PATH).ok()?;
+
+        match ron::from_str(&contents) {
+            Ok(save) => Some(save),
+            Err(err) => {
+                warn!("Ignoring unreadable save file {}: {}", SAVE_PATH, err);
+                None
+            }
+        }
+    }
+
+    fn write(&self) {
+        let result = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(SAVE_PATH, contents).map_err(|err| err.to_string()));
+
+        if let Err(err) = result {
+            warn!("Couldn't write save file {}: {}", SAVE_PATH, err);
+        }
+    }
+}
+
+/// Whether the save file has been applied, it's only read once per run so respawns and level
+/// reloads don't throw away progress made since the last save
+#[derive(Resource, Default)]
+struct SaveLoaded(bool);
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveLoaded>().add_systems(
+            Update,
+            (load_save.run_if(in_state(GameState::Playing)), save_game),
+        );
+    }
+}
+
+/// Applies the save file to the first player once the game is playing
+fn load_save(
+    mut player: Query<&mut Level, With<Player>>,
+    mut loaded: ResMut<SaveLoaded>,
+    mut signals: ResMut<Signals>,
+) {
+    if loaded.0 {
+        return;
+    }
+
+    let Ok(mut level) = player.get_single_mut() else {
+        return;
+    };
+
+    loaded.0 = true;
+
+    if let Some(save) = SaveData::read() {
+        level.level = save.level.max(1);
+        level.experience = save.experience;
+
+        for (lever, on) in save.levels.values().flat_map(|level| &level.levers) {
+            signals.restore_lever(lever, *on);
+        }
+    }
+}
+
+/// Saves on F5 and automatically after every level-up or pulled lever
+fn save_game(
+    player: Query<&Level, With<Player>>,
+    signals: Res<Signals>,
+    keyb: Res<Input<KeyCode>>,
+    mut level_up: EventReader<LevelUp>,
+    mut lever_pulled: EventReader<LeverPulled>,
+) {
+    let leveled_up = level_up.read().count() > 0;
+    let lever_pulled = lever_pulled.read().count() > 0;
+
+    if !leveled_up && !lever_pulled && !keyb.just_pressed(KeyCode::F5) {
+        return;
+    }
+
+    let Ok(level) = player.get_single() else {
+        return;
+    };
+
+    let mut levels: BTreeMap<String, LevelSave> = BTreeMap::new();
+
+    for (level_iid, lever, on) in signals.levers() {
+        levels
+            .entry(level_iid.to_string())
+            .or_default()
+            .levers
+            .insert(lever.to_string(), on);
+    }
+
+    SaveData {
+        level: level.level,
+        experience: level.experience,
+        levels,
+    }
+    .write();
+}
@@ -0,0 +1,380 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    collision::{entity_collider, Layer},
+    day_night::DayNight,
+    loading::SpriteSheets,
+    Enemy1Bundle, Enemy2Bundle, EnemyState, GameState, Player,
+};
+
+/// Spawns a number of enemies, one every `interval`, once it's triggered
+/// Spawners with a `wave` above 0 belong to the wave arena in their level and are triggered by it
+#[derive(Component)]
+pub struct Spawner {
+    archetype: String,
+    remaining: i32,
+    interval: Timer,
+    wave: i32,
+    /// Holds off until nightfall, for night-only archetypes
+    night_only: bool,
+    active: bool,
+}
+
+impl Default for Spawner {
+    fn default() -> Self {
+        Self {
+            archetype: "satyr".to_string(),
+            remaining: 3,
+            interval: Timer::from_seconds(2.0, TimerMode::Repeating),
+            wave: 0,
+            night_only: false,
+            active: false,
+        }
+    }
+}
+
+impl Spawner {
+    fn from_entity_instance(entity_instance: &EntityInstance) -> Self {
+        let default = Self::default();
+
+        Self {
+            archetype: entity_instance
+                .get_string_field("archetype")
+                .cloned()
+                .unwrap_or(default.archetype),
+            remaining: entity_instance
+                .get_int_field("count")
+                .copied()
+                .unwrap_or(default.remaining),
+            interval: entity_instance
+                .get_float_field("interval")
+                .map(|seconds| Timer::from_seconds(seconds.max(0.1), TimerMode::Repeating))
+                .unwrap_or(default.interval),
+            wave: entity_instance
+                .get_int_field("wave")
+                .copied()
+                .unwrap_or(default.wave),
+            night_only: entity_instance
+                .get_bool_field("night_only")
+                .copied()
+                .unwrap_or(default.night_only),
+            active: false,
+        }
+    }
+
+    /// Starts spawning, the first enemy shows up right away
+    fn activate(&mut self) {
+        self.active = true;
+
+        let duration = self.interval.duration();
+        self.interval.set_elapsed(duration);
+    }
+
+    fn exhausted(&self) -> bool {
+        self.remaining <= 0
+    }
+}
+
+/// The area the player has to enter to trigger a spawner, from the `trigger_width` and
+/// `trigger_height` fields or the spawner's own size
+fn trigger_area(entity_instance: &EntityInstance) -> Collider {
+    let size = |field: &str, fallback: i32| {
+        entity_instance
+            .get_int_field(field)
+            .copied()
+            .unwrap_or(fallback) as f32
+    };
+
+    Collider::cuboid(
+        size("trigger_width", entity_instance.width) / 2.0,
+        size("trigger_height", entity_instance.height) / 2.0,
+    )
+}
+
+/// Which spawner an enemy came from
+#[derive(Component)]
+struct SpawnedBy(Entity);
+
+#[derive(Bundle, LdtkEntity)]
+struct SpawnerBundle {
+    #[with(Spawner::from_entity_instance)]
+    spawner: Spawner,
+    #[with(trigger_area)]
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for SpawnerBundle {
+    fn default() -> Self {
+        Self {
+            spawner: Spawner::default(),
+            collider: Collider::cuboid(8.0, 8.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+enum WaveState {
+    #[default]
+    Waiting,
+    Wave(i32),
+    Cleared,
+}
+
+/// Entering this region closes its gates and runs the waves of every spawner in the level
+#[derive(Default, Component)]
+struct WaveArena {
+    state: WaveState,
+}
+
+#[derive(Bundle, LdtkEntity)]
+struct WaveArenaBundle {
+    arena: WaveArena,
+    #[with(entity_collider)]
+    collider: Collider,
+    sensor: Sensor,
+    collision_group: CollisionGroups,
+}
+
+impl Default for WaveArenaBundle {
+    fn default() -> Self {
+        Self {
+            arena: WaveArena::default(),
+            collider: Collider::cuboid(8.0, 8.0),
+            sensor: Sensor,
+            collision_group: Layer::Sensor.collision_groups(),
+        }
+    }
+}
+
+/// Blocks a wave arena's exit until every wave is cleared
+#[derive(Default, Component)]
+struct ArenaGate;
+
+#[derive(Bundle, LdtkEntity)]
+struct ArenaGateBundle {
+    gate: ArenaGate,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(entity_collider)]
+    collider: Collider,
+    // Gates stay open until the arena is entered
+    disabled: ColliderDisabled,
+    rigid_body: RigidBody,
+    collision_group: CollisionGroups,
+    solver_group: SolverGroups,
+}
+
+impl Default for ArenaGateBundle {
+    fn default() -> Self {
+        Self {
+            gate: ArenaGate,
+            sprite_sheet_bundle: SpriteSheetBundle::default(),
+            collider: Collider::cuboid(4.0, 8.0),
+            disabled: ColliderDisabled,
+            rigid_body: RigidBody::Fixed,
+            collision_group: Layer::World.collision_groups(),
+            solver_group: Layer::World.solver_groups(),
+        }
+    }
+}
+
+pub struct SpawnersPlugin;
+
+impl Plugin for SpawnersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                hide_open_gates,
+                trigger_spawners,
+                run_wave_arenas,
+                run_spawners,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        )
+        .register_ldtk_entity::<SpawnerBundle>("Spawner")
+        .register_ldtk_entity::<WaveArenaBundle>("WaveArena")
+        .register_ldtk_entity::<ArenaGateBundle>("ArenaGate");
+    }
+}
+
+fn hide_open_gates(mut gates: Query<&mut Visibility, Added<ArenaGate>>) {
+    for mut visibility in gates.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Spawners outside of arenas start when the player walks into their trigger area
+fn trigger_spawners(
+    mut spawners: Query<(Entity, &mut Spawner)>,
+    player: Query<Entity, With<Player>>,
+    rapier_context: Res<RapierContext>,
+) {
+    let Ok(player_entity) = player.get_single() else {
+        return;
+    };
+
+    for (entity, mut spawner) in spawners.iter_mut() {
+        if spawner.active || spawner.wave > 0 {
+            continue;
+        }
+
+        if rapier_context.intersection_pair(player_entity, entity) == Some(true) {
+            spawner.activate();
+        }
+    }
+}
+
+fn run_wave_arenas(
+    mut commands: Commands,
+    mut arenas: Query<(Entity, &mut WaveArena, &Parent)>,
+    mut spawners: Query<(Entity, &mut Spawner, &Parent)>,
+    gates: Query<(Entity, &Parent), With<ArenaGate>>,
+    spawned: Query<(&SpawnedBy, &EnemyState)>,
+    parents: Query<&Parent>,
+    player: Query<Entity, With<Player>>,
+    rapier_context: Res<RapierContext>,
+) {
+    let Ok(player_entity) = player.get_single() else {
+        return;
+    };
+
+    // Arenas, spawners and gates are children of a layer, which is a child of the level
+    let level_of = |parent: &Parent| parents.get(parent.get()).ok().map(|level| level.get());
+
+    for (arena_entity, mut arena, arena_parent) in arenas.iter_mut() {
+        let level = level_of(arena_parent);
+
+        let next_wave = match arena.state {
+            WaveState::Cleared => continue,
+            WaveState::Waiting => {
+                if rapier_context.intersection_pair(player_entity, arena_entity) != Some(true) {
+                    continue;
+                }
+
+                for (gate, _) in gates.iter().filter(|(_, parent)| level_of(parent) == level) {
+                    commands
+                        .entity(gate)
+                        .remove::<ColliderDisabled>()
+                        .insert(Visibility::Visible);
+                }
+
+                1
+            }
+            WaveState::Wave(wave) => {
+                let wave_spawners: Vec<(Entity, bool)> = spawners
+                    .iter()
+                    .filter(|(_, spawner, parent)| {
+                        spawner.wave == wave && level_of(parent) == level
+                    })
+                    .map(|(entity, spawner, _)| (entity, spawner.exhausted()))
+                    .collect();
+
+                let unfinished = wave_spawners.iter().any(|(_, exhausted)| !exhausted);
+
+                let alive = spawned.iter().any(|(spawned_by, state)| {
+                    wave_spawners
+                        .iter()
+                        .any(|(entity, _)| *entity == spawned_by.0)
+                        && !matches!(state, EnemyState::Dying | EnemyState::Dead(_))
+                });
+
+                if unfinished || alive {
+                    continue;
+                }
+
+                wave + 1
+            }
+        };
+
+        let mut started = false;
+
+        for (_, mut spawner, parent) in spawners.iter_mut() {
+            if spawner.wave == next_wave && level_of(parent) == level {
+                spawner.activate();
+                started = true;
+            }
+        }
+
+        if started {
+            arena.state = WaveState::Wave(next_wave);
+            continue;
+        }
+
+        // No spawners left for the next wave, the arena is done
+        arena.state = WaveState::Cleared;
+
+        for (gate, _) in gates.iter().filter(|(_, parent)| level_of(parent) == level) {
+            commands
+                .entity(gate)
+                .insert((ColliderDisabled, Visibility::Hidden));
+        }
+    }
+}
+
+fn run_spawners(
+    mut commands: Commands,
+    mut spawners: Query<(Entity, &mut Spawner, &Transform, &Parent)>,
+    sprite_sheets: Res<SpriteSheets>,
+    day_night: Res<DayNight>,
+    time: Res<Time>,
+) {
+    for (entity, mut spawner, transform, parent) in spawners.iter_mut() {
+        if !spawner.active || spawner.exhausted() || (spawner.night_only && !day_night.is_night()) {
+            continue;
+        }
+
+        spawner.interval.tick(time.delta());
+
+        if !spawner.interval.just_finished() {
+            continue;
+        }
+
+        let sprite_sheet =
+            |texture_atlas: &Handle<TextureAtlas>, index: usize, size: f32| SpriteSheetBundle {
+                texture_atlas: texture_atlas.clone(),
+                sprite: TextureAtlasSprite {
+                    index,
+                    custom_size: Some(Vec2::splat(size)),
+                    ..Default::default()
+                },
+                transform: *transform,
+                ..Default::default()
+            };
+
+        let enemy = match spawner.archetype.as_str() {
+            "satyr" => commands
+                .spawn(Enemy1Bundle {
+                    sprite_sheet_bundle: sprite_sheet(&sprite_sheets.get("satyr"), 0, 50.0),
+                    ..Default::default()
+                })
+                .id(),
+            "lizard" => commands
+                .spawn(Enemy2Bundle {
+                    sprite_sheet_bundle: sprite_sheet(&sprite_sheets.get("lizard"), 39, 16.0),
+                    ..Default::default()
+                })
+                .id(),
+            archetype => {
+                warn!("Spawner {entity:?} has unknown archetype {archetype:?}");
+                spawner.remaining = 0;
+                continue;
+            }
+        };
+
+        // Spawn next to the spawner so the enemy ends up in the same level and layer
+        commands
+            .entity(enemy)
+            .insert(SpawnedBy(entity))
+            .set_parent(parent.get());
+
+        spawner.remaining -= 1;
+    }
+}